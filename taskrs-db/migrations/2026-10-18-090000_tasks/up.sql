@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+CREATE TABLE tasks
+(
+    id          SERIAL PRIMARY KEY         NOT NULL,
+    project_id  INTEGER                    NOT NULL,
+    title       VARCHAR(256)               NOT NULL,
+    description TEXT,
+    status      VARCHAR(32) DEFAULT 'open' NOT NULL,
+    priority    SMALLINT    DEFAULT 1      NOT NULL,
+    assignee_id INTEGER,
+    reporter_id INTEGER,
+    due_date    TIMESTAMP,
+    updated_at  TIMESTAMP   DEFAULT now(),
+    created_at  TIMESTAMP   DEFAULT now(),
+
+    FOREIGN KEY (project_id) REFERENCES projects (id),
+    FOREIGN KEY (assignee_id) REFERENCES users (id),
+    FOREIGN KEY (reporter_id) REFERENCES users (id)
+);
@@ -0,0 +1,36 @@
+-- Your SQL goes here
+
+CREATE TABLE roles
+(
+    id          SERIAL PRIMARY KEY  NOT NULL,
+    name        VARCHAR(128) UNIQUE NOT NULL,
+    description VARCHAR(512),
+    updated_at  TIMESTAMP DEFAULT now(),
+    created_at  TIMESTAMP DEFAULT now()
+);
+
+CREATE TABLE role_permissions
+(
+    role_id       INTEGER NOT NULL,
+    permission_id INTEGER NOT NULL,
+    updated_at    TIMESTAMP DEFAULT now(),
+    created_at    TIMESTAMP DEFAULT now(),
+
+    PRIMARY KEY (role_id, permission_id),
+
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+    FOREIGN KEY (permission_id) REFERENCES permissions (id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_roles
+(
+    user_id    INTEGER NOT NULL,
+    role_id    INTEGER NOT NULL,
+    updated_at TIMESTAMP DEFAULT now(),
+    created_at TIMESTAMP DEFAULT now(),
+
+    PRIMARY KEY (user_id, role_id),
+
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE
+);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+CREATE TABLE password_reset_tokens
+(
+    id         SERIAL PRIMARY KEY  NOT NULL,
+    user_id    INTEGER             NOT NULL,
+    token_hash VARCHAR(128) UNIQUE NOT NULL,
+    exp        BIGINT              NOT NULL,
+    used_at    TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT now(),
+    created_at TIMESTAMP DEFAULT now(),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
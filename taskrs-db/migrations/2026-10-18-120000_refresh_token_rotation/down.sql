@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX auth_refresh_tokens_family_idx;
+
+ALTER TABLE auth_refresh_tokens
+    ALTER COLUMN token TYPE VARCHAR(256),
+    DROP COLUMN family,
+    DROP COLUMN rotated_at;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+ALTER TABLE auth_refresh_tokens
+    ADD COLUMN family     VARCHAR(64),
+    ADD COLUMN rotated_at TIMESTAMP;
+
+-- Every existing token starts its own family
+UPDATE auth_refresh_tokens
+SET family = id::TEXT;
+
+ALTER TABLE auth_refresh_tokens
+    ALTER COLUMN family SET NOT NULL;
+
+-- Room for the jti claim
+ALTER TABLE auth_refresh_tokens
+    ALTER COLUMN token TYPE VARCHAR(512);
+
+CREATE INDEX auth_refresh_tokens_family_idx ON auth_refresh_tokens (family);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+ALTER TABLE auth_refresh_tokens
+    ADD COLUMN user_agent   VARCHAR(512),
+    ADD COLUMN ip           VARCHAR(64),
+    ADD COLUMN last_used_at TIMESTAMP DEFAULT now();
+
+UPDATE auth_refresh_tokens
+SET last_used_at = created_at;
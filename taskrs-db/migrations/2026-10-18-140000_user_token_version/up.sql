@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE users
+    ADD COLUMN token_version INTEGER DEFAULT 0 NOT NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE totp_recovery_codes;
+DROP TABLE user_totp;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+CREATE TABLE user_totp
+(
+    user_id        INTEGER PRIMARY KEY    NOT NULL,
+    secret         VARCHAR(64)            NOT NULL,
+    confirmed      BOOLEAN DEFAULT false  NOT NULL,
+    last_used_step BIGINT,
+    updated_at     TIMESTAMP DEFAULT now(),
+    created_at     TIMESTAMP DEFAULT now(),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE totp_recovery_codes
+(
+    id         SERIAL PRIMARY KEY NOT NULL,
+    user_id    INTEGER            NOT NULL,
+    code_hash  VARCHAR(128)       NOT NULL,
+    used_at    TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT now(),
+    created_at TIMESTAMP DEFAULT now(),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE api_token_permissions;
+DROP TABLE api_tokens;
@@ -0,0 +1,28 @@
+-- Your SQL goes here
+
+CREATE TABLE api_tokens
+(
+    id           SERIAL PRIMARY KEY  NOT NULL,
+    user_id      INTEGER             NOT NULL,
+    name         VARCHAR(128)        NOT NULL,
+    token_hash   VARCHAR(128) UNIQUE NOT NULL,
+    exp          BIGINT,
+    last_used_at TIMESTAMP,
+    updated_at   TIMESTAMP DEFAULT now(),
+    created_at   TIMESTAMP DEFAULT now(),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE api_token_permissions
+(
+    api_token_id  INTEGER NOT NULL,
+    permission_id INTEGER NOT NULL,
+    updated_at    TIMESTAMP DEFAULT now(),
+    created_at    TIMESTAMP DEFAULT now(),
+
+    PRIMARY KEY (api_token_id, permission_id),
+
+    FOREIGN KEY (api_token_id) REFERENCES api_tokens (id) ON DELETE CASCADE,
+    FOREIGN KEY (permission_id) REFERENCES permissions (id) ON DELETE CASCADE
+);
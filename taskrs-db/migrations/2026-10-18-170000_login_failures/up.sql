@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE login_failures
+(
+    id           SERIAL PRIMARY KEY     NOT NULL,
+    scope        VARCHAR(16)            NOT NULL,
+    identifier   VARCHAR(128)           NOT NULL,
+    failures     INTEGER   DEFAULT 0    NOT NULL,
+    locked_until BIGINT,
+    last_failure BIGINT                 NOT NULL,
+    updated_at   TIMESTAMP DEFAULT now(),
+    created_at   TIMESTAMP DEFAULT now(),
+
+    UNIQUE (scope, identifier)
+);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE user_identities
+(
+    id         SERIAL PRIMARY KEY NOT NULL,
+    user_id    INTEGER            NOT NULL,
+    issuer     VARCHAR(255)       NOT NULL,
+    subject    VARCHAR(255)       NOT NULL,
+    updated_at TIMESTAMP DEFAULT now(),
+    created_at TIMESTAMP DEFAULT now(),
+
+    UNIQUE (issuer, subject),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
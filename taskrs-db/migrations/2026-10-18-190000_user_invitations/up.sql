@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE user_invitations
+(
+    id         SERIAL PRIMARY KEY  NOT NULL,
+    user_id    INTEGER             NOT NULL,
+    token_hash VARCHAR(128) UNIQUE NOT NULL,
+    exp        BIGINT              NOT NULL,
+    invited_by INTEGER,
+    used_at    TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT now(),
+    created_at TIMESTAMP DEFAULT now(),
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER IF EXISTS set_updated_at ON tasks;
+DROP TRIGGER IF EXISTS set_updated_at ON projects;
+DROP TRIGGER IF EXISTS set_updated_at ON categories;
+DROP TRIGGER IF EXISTS set_updated_at ON users;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- updated_at is the version used for optimistic concurrency
+SELECT diesel_manage_updated_at('users');
+SELECT diesel_manage_updated_at('categories');
+SELECT diesel_manage_updated_at('projects');
+SELECT diesel_manage_updated_at('tasks');
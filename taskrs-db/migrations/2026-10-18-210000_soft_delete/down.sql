@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE projects
+    DROP COLUMN deleted_at;
+
+ALTER TABLE categories
+    DROP COLUMN deleted_at;
+
+ALTER TABLE users
+    DROP COLUMN deleted_at;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+-- Deleted rows are kept until they are purged by the maintenance task
+ALTER TABLE users
+    ADD COLUMN deleted_at TIMESTAMP;
+
+ALTER TABLE categories
+    ADD COLUMN deleted_at TIMESTAMP;
+
+ALTER TABLE projects
+    ADD COLUMN deleted_at TIMESTAMP;
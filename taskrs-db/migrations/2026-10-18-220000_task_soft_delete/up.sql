@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- Tasks go to the trash together with their project
+ALTER TABLE tasks
+    ADD COLUMN deleted_at TIMESTAMP;
@@ -10,10 +10,12 @@ use log::{debug, error, trace};
 use r2d2::Pool;
 
 use models::permission::{NewUserPermission, Permission};
+use models::role::{NewRolePermission, Role, RoleSeed};
 use models::user::User;
 
 pub mod models;
 pub mod schema;
+pub mod token;
 
 pub type Db = Pg;
 pub type DbConnection = PgConnection;
@@ -36,9 +38,8 @@ pub fn connect_database(
     trace!("Database URL: {}", &database_url);
 
     let connection_manager = ConnectionManager::<PgConnection>::new(&database_url);
-    Pool::builder().build(connection_manager).map_err(|e| {
-        error!("Could not create database connections: {}", &e);
-        e
+    Pool::builder().build(connection_manager).inspect_err(|e| {
+        error!("Could not create database connections: {}", e);
     })
 }
 
@@ -66,6 +67,8 @@ pub fn seed_root_permissions(
             activated: true,
             updated_at: None,
             created_at: None,
+            token_version: 0,
+            deleted_at: None,
         };
 
         new_root_user.hash_password()?;
@@ -104,6 +107,54 @@ pub fn seed_root_permissions(
     Ok(())
 }
 
+pub fn seed_roles(roles: &[RoleSeed], conn: &DbConnection) -> anyhow::Result<()> {
+    use schema::{permissions, role_permissions};
+
+    for seed in roles {
+        let role = match Role::find_by_name(&seed.name, conn)? {
+            Some(role) => role,
+            None => {
+                debug!("Seeding role '{}'", &seed.name);
+                Role {
+                    id: 0,
+                    name: seed.name.clone(),
+                    description: seed.description.clone(),
+                    updated_at: None,
+                    created_at: None,
+                }
+                .insert(conn)?
+            }
+        };
+
+        // Add permissions the role does not have yet
+        let missing_permissions: Vec<i32> = permissions::table
+            .select(permissions::id)
+            .filter(permissions::name.eq_any(&seed.permissions))
+            .filter(
+                permissions::id.ne_all(
+                    role_permissions::table
+                        .select(role_permissions::permission_id)
+                        .filter(role_permissions::role_id.eq(role.id)),
+                ),
+            )
+            .load::<i32>(conn)?;
+
+        let new_role_permissions = missing_permissions
+            .into_iter()
+            .map(|permission_id| NewRolePermission {
+                role_id: role.id,
+                permission_id,
+            })
+            .collect::<Vec<NewRolePermission>>();
+
+        diesel::insert_into(role_permissions::table)
+            .values(new_role_permissions)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
 pub fn update_permissions(
     all_permissions: Vec<&Permission>,
     conn: &DbConnection,
@@ -120,7 +171,7 @@ pub fn update_permissions(
         all_permissions.iter().map(|x| x.name.clone()).collect();
 
     let mapped_permissions: Vec<(Option<&&Permission>, Option<&Permission>)> =
-        vec![db_permission_names, all_permission_names]
+        [db_permission_names, all_permission_names]
             .iter()
             .flatten()
             .map(|name| {
@@ -165,3 +216,152 @@ pub fn update_permissions(
 
     Ok(())
 }
+
+/// Deletes expired refresh tokens, password reset tokens and invitations.
+/// Returns the number of deleted refresh tokens, password reset tokens and invitations.
+pub fn purge_expired_tokens(conn: &DbConnection) -> anyhow::Result<(usize, usize, usize)> {
+    use schema::{auth_refresh_tokens, password_reset_tokens, user_invitations};
+
+    let now = chrono::Utc::now().timestamp();
+
+    debug!("Deleting expired refresh tokens");
+    let refresh_tokens =
+        diesel::delete(auth_refresh_tokens::table.filter(auth_refresh_tokens::exp.lt(now)))
+            .execute(conn)?;
+
+    debug!("Deleting expired password reset tokens");
+    let password_reset_tokens =
+        diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::exp.lt(now)))
+            .execute(conn)?;
+
+    debug!("Deleting expired invitations");
+    let invitations = diesel::delete(user_invitations::table.filter(user_invitations::exp.lt(now)))
+        .execute(conn)?;
+
+    Ok((refresh_tokens, password_reset_tokens, invitations))
+}
+
+/// Deletes login failures whose last failure and lockout ended more than `window` seconds ago
+pub fn purge_login_failures(window: i64, conn: &DbConnection) -> diesel::QueryResult<usize> {
+    use diesel::BoolExpressionMethods;
+    use schema::login_failures;
+
+    let before = chrono::Utc::now().timestamp() - window;
+
+    debug!("Deleting outdated login failures");
+    diesel::delete(
+        login_failures::table
+            .filter(login_failures::last_failure.lt(before))
+            .filter(
+                login_failures::locked_until
+                    .is_null()
+                    .or(login_failures::locked_until.lt(before)),
+            ),
+    )
+    .execute(conn)
+}
+
+/// Permanently deletes users, projects and categories that were deleted more than
+/// `retention` seconds ago. Rows that are still referenced are kept until a later run.
+/// Returns the number of purged users, projects and categories.
+pub fn purge_deleted(retention: i64, conn: &DbConnection) -> anyhow::Result<(usize, usize, usize)> {
+    use diesel::dsl::now;
+    use diesel::pg::expression::extensions::IntervalDsl;
+    use diesel::NullableExpressionMethods;
+    use schema::{
+        auth_refresh_tokens, categories, project_members, projects, tasks, user_permissions,
+        user_roles, users,
+    };
+
+    let deleted_before = (now - retention.seconds()).nullable();
+
+    debug!("Purging deleted projects");
+    let project_ids: Vec<i32> = projects::table
+        .filter(projects::deleted_at.lt(deleted_before))
+        .select(projects::id)
+        .load(conn)?;
+    let mut purged_projects = 0;
+    for id in project_ids {
+        purged_projects += purge_row("project", id, conn, || {
+            diesel::delete(tasks::table.filter(tasks::project_id.eq(id))).execute(conn)?;
+            diesel::delete(project_members::table.filter(project_members::project_id.eq(id)))
+                .execute(conn)?;
+            diesel::delete(projects::table.find(id)).execute(conn)
+        })?;
+    }
+
+    debug!("Purging deleted users");
+    let user_ids: Vec<i32> = users::table
+        .filter(users::deleted_at.lt(deleted_before))
+        .select(users::id)
+        .load(conn)?;
+    let mut purged_users = 0;
+    for id in user_ids {
+        // Owned projects keep the user until they are transferred or purged
+        purged_users += purge_row("user", id, conn, || {
+            diesel::delete(auth_refresh_tokens::table.filter(auth_refresh_tokens::user_id.eq(id)))
+                .execute(conn)?;
+            diesel::delete(user_permissions::table.filter(user_permissions::user_id.eq(id)))
+                .execute(conn)?;
+            diesel::delete(user_roles::table.filter(user_roles::user_id.eq(id))).execute(conn)?;
+            diesel::delete(project_members::table.filter(project_members::user_id.eq(id)))
+                .execute(conn)?;
+            diesel::update(tasks::table.filter(tasks::assignee_id.eq(id)))
+                .set(tasks::assignee_id.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::update(tasks::table.filter(tasks::reporter_id.eq(id)))
+                .set(tasks::reporter_id.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::update(projects::table.filter(projects::creator_id.eq(id)))
+                .set(projects::creator_id.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::delete(users::table.find(id)).execute(conn)
+        })?;
+    }
+
+    debug!("Purging deleted categories");
+    let mut purged_categories = 0;
+    // Sub categories have to be purged before their parents
+    loop {
+        let category_ids: Vec<i32> = categories::table
+            .filter(categories::deleted_at.lt(deleted_before))
+            .select(categories::id)
+            .load(conn)?;
+        let mut purged = 0;
+        for id in category_ids {
+            purged += purge_row("category", id, conn, || {
+                diesel::delete(categories::table.find(id)).execute(conn)
+            })?;
+        }
+
+        purged_categories += purged;
+        if purged == 0 {
+            break;
+        }
+    }
+
+    Ok((purged_users, purged_projects, purged_categories))
+}
+
+/// Runs `purge` in its own transaction.
+/// Returns 0 instead of failing if the row is still referenced.
+fn purge_row<F>(entity: &str, id: i32, conn: &DbConnection, purge: F) -> diesel::QueryResult<usize>
+where
+    F: FnOnce() -> diesel::QueryResult<usize>,
+{
+    use diesel::result::{DatabaseErrorKind, Error};
+    use diesel::Connection;
+
+    match conn.transaction(purge) {
+        Err(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info)) => {
+            debug!(
+                "Deleted {} {} is still referenced: {}",
+                entity,
+                id,
+                info.message()
+            );
+            Ok(0)
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{api_token_permissions, api_tokens};
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub exp: Option<i64>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl ApiToken {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<ApiToken> {
+        let new_api_token: NewApiToken = self.into();
+        diesel::insert_into(api_tokens::table)
+            .values(new_api_token)
+            .get_result(conn)
+    }
+
+    pub fn find_by_hash(q: &str, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        api_tokens::table
+            .filter(api_tokens::token_hash.eq(q))
+            .first::<Self>(conn)
+            .optional()
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "api_tokens"]
+struct NewApiToken {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub exp: Option<i64>,
+}
+
+impl From<ApiToken> for NewApiToken {
+    fn from(
+        ApiToken {
+            user_id,
+            name,
+            token_hash,
+            exp,
+            ..
+        }: ApiToken,
+    ) -> Self {
+        Self {
+            user_id,
+            name,
+            token_hash,
+            exp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTokenPermission {
+    pub api_token_id: i32,
+    pub permission_id: i32,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "api_token_permissions"]
+pub struct NewApiTokenPermission {
+    pub api_token_id: i32,
+    pub permission_id: i32,
+}
@@ -15,6 +15,11 @@ pub struct AuthRefreshToken {
     pub exp: i64,
     pub updated_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
+    pub family: String,
+    pub rotated_at: Option<NaiveDateTime>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub last_used_at: Option<NaiveDateTime>,
 }
 
 impl AuthRefreshToken {
@@ -40,6 +45,9 @@ struct NewAuthRefreshToken {
     pub token: String,
     pub iat: i64,
     pub exp: i64,
+    pub family: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
 }
 
 impl From<AuthRefreshToken> for NewAuthRefreshToken {
@@ -49,6 +57,9 @@ impl From<AuthRefreshToken> for NewAuthRefreshToken {
             token,
             iat,
             exp,
+            family,
+            user_agent,
+            ip,
             ..
         }: AuthRefreshToken,
     ) -> Self {
@@ -57,6 +68,9 @@ impl From<AuthRefreshToken> for NewAuthRefreshToken {
             token,
             iat,
             exp,
+            family,
+            user_agent,
+            ip,
         }
     }
 }
@@ -1,10 +1,11 @@
 use chrono::NaiveDateTime;
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable,
-    RunQueryDsl,
+    AsChangeset, BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl,
+    Queryable, QueryableByName, RunQueryDsl,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::models::double_option;
 use crate::schema::categories;
 use crate::DbConnection;
 
@@ -16,16 +17,20 @@ pub enum CategoryColumns {
     ParentCategoryId,
     UpdatedAt,
     CreatedAt,
+    DeletedAt,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable, QueryableByName)]
 #[serde(rename_all = "camelCase")]
+#[table_name = "categories"]
 pub struct Category {
     pub id: i32,
     pub name: String,
     pub parent_category_id: Option<i32>,
     pub updated_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
+    /// Set while the entity is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 impl Category {
@@ -70,3 +75,20 @@ impl From<Category> for NewCategory {
         }
     }
 }
+
+/// Changes to a category, missing fields are not updated
+#[derive(Debug, Clone, Default, Serialize, Deserialize, AsChangeset)]
+#[serde(rename_all = "camelCase")]
+#[table_name = "categories"]
+pub struct CategoryChangeset {
+    pub id: i32,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub parent_category_id: Option<Option<i32>>,
+}
+
+impl CategoryChangeset {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.parent_category_id.is_none()
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::NaiveDateTime;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::login_failures;
+use crate::DbConnection;
+
+/// Failures are tracked per email
+pub const SCOPE_EMAIL: &str = "email";
+/// Failures are tracked per client IP
+pub const SCOPE_IP: &str = "ip";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginFailure {
+    pub id: i32,
+    pub scope: String,
+    pub identifier: String,
+    pub failures: i32,
+    pub locked_until: Option<i64>,
+    pub last_failure: i64,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl LoginFailure {
+    /// Counts a failure for scope and identifier and returns the updated row.
+    /// Failures older than `window` seconds are forgotten.
+    pub fn increment(
+        scope: &str,
+        identifier: &str,
+        now: i64,
+        window: i64,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<LoginFailure> {
+        // Restart counting if the last failure and lockout are outside the window
+        diesel::update(
+            login_failures::table
+                .filter(login_failures::scope.eq(scope))
+                .filter(login_failures::identifier.eq(identifier))
+                .filter(login_failures::last_failure.lt(now - window))
+                .filter(
+                    login_failures::locked_until
+                        .is_null()
+                        .or(login_failures::locked_until.lt(now - window)),
+                ),
+        )
+        .set(login_failures::failures.eq(0))
+        .execute(conn)?;
+
+        // Increment in the database, so concurrent failures are all counted
+        diesel::insert_into(login_failures::table)
+            .values(&NewLoginFailure {
+                scope: scope.to_string(),
+                identifier: identifier.to_string(),
+                failures: 1,
+                locked_until: None,
+                last_failure: now,
+            })
+            .on_conflict((login_failures::scope, login_failures::identifier))
+            .do_update()
+            .set((
+                login_failures::failures.eq(login_failures::failures + 1),
+                login_failures::last_failure.eq(now),
+            ))
+            .get_result(conn)
+    }
+
+    /// Locks the login until `locked_until`, an existing longer lockout is kept
+    pub fn lock(id: i32, locked_until: i64, conn: &DbConnection) -> diesel::QueryResult<usize> {
+        diesel::update(
+            login_failures::table.find(id).filter(
+                login_failures::locked_until
+                    .is_null()
+                    .or(login_failures::locked_until.lt(locked_until)),
+            ),
+        )
+        .set(login_failures::locked_until.eq(locked_until))
+        .execute(conn)
+    }
+
+    pub fn find(
+        scope: &str,
+        identifier: &str,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Self>> {
+        login_failures::table
+            .filter(login_failures::scope.eq(scope))
+            .filter(login_failures::identifier.eq(identifier))
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    pub fn delete(
+        scope: &str,
+        identifier: &str,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<usize> {
+        diesel::delete(
+            login_failures::table
+                .filter(login_failures::scope.eq(scope))
+                .filter(login_failures::identifier.eq(identifier)),
+        )
+        .execute(conn)
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "login_failures"]
+struct NewLoginFailure {
+    pub scope: String,
+    pub identifier: String,
+    pub failures: i32,
+    pub locked_until: Option<i64>,
+    pub last_failure: i64,
+}
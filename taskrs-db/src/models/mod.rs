@@ -1,5 +1,25 @@
+use serde::{Deserialize, Deserializer};
+
+pub mod api_token;
 pub mod auth_refresh_token;
 pub mod category;
+pub mod login_failure;
+pub mod password_reset_token;
 pub mod permission;
 pub mod project;
+pub mod role;
+pub mod task;
+pub mod totp;
 pub mod user;
+pub mod user_identity;
+pub mod user_invitation;
+
+/// Deserializes a nullable field of a changeset.
+/// A missing field leaves the column unchanged (`None`), `null` clears it (`Some(None)`).
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
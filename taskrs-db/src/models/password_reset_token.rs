@@ -0,0 +1,59 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::password_reset_tokens;
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub exp: i64,
+    pub used_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl PasswordResetToken {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<PasswordResetToken> {
+        let new_password_reset_token: NewPasswordResetToken = self.into();
+        diesel::insert_into(password_reset_tokens::table)
+            .values(new_password_reset_token)
+            .get_result(conn)
+    }
+
+    pub fn find_by_hash(q: &str, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        password_reset_tokens::table
+            .filter(password_reset_tokens::token_hash.eq(q))
+            .first::<Self>(conn)
+            .optional()
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "password_reset_tokens"]
+struct NewPasswordResetToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub exp: i64,
+}
+
+impl From<PasswordResetToken> for NewPasswordResetToken {
+    fn from(
+        PasswordResetToken {
+            user_id,
+            token_hash,
+            exp,
+            ..
+        }: PasswordResetToken,
+    ) -> Self {
+        Self {
+            user_id,
+            token_hash,
+            exp,
+        }
+    }
+}
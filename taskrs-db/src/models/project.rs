@@ -1,7 +1,10 @@
 use chrono::NaiveDateTime;
-use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use diesel::{
+    AsChangeset, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::models::double_option;
 use crate::schema::{project_members, projects};
 use crate::DbConnection;
 
@@ -16,6 +19,7 @@ pub enum ProjectColumns {
     CreatorId,
     UpdatedAt,
     CreatedAt,
+    DeletedAt,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
@@ -29,6 +33,8 @@ pub struct Project {
     pub creator_id: Option<i32>,
     pub updated_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
+    /// Set while the entity is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 impl Project {
@@ -79,6 +85,28 @@ impl From<Project> for NewProject {
     }
 }
 
+/// Changes to a project, missing fields are not updated
+#[derive(Debug, Clone, Default, Serialize, Deserialize, AsChangeset)]
+#[serde(rename_all = "camelCase")]
+#[table_name = "projects"]
+pub struct ProjectChangeset {
+    pub id: i32,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    pub category_id: Option<i32>,
+    pub owner_id: Option<i32>,
+}
+
+impl ProjectChangeset {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.category_id.is_none()
+            && self.owner_id.is_none()
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectMember {
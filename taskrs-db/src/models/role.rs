@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{role_permissions, roles, user_roles};
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoleColumns {
+    Id,
+    Name,
+    Description,
+    UpdatedAt,
+    CreatedAt,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Role {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<Role> {
+        let new_role: NewRole = self.into();
+        diesel::insert_into(roles::table)
+            .values(new_role)
+            .get_result(conn)
+    }
+
+    pub fn find_by_name(q: &str, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        roles::table
+            .filter(roles::name.eq(q))
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    pub fn exists(&self, conn: &DbConnection) -> diesel::QueryResult<bool> {
+        Self::find_by_name(&self.name, conn).map(|role| role.is_some())
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "roles"]
+struct NewRole {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+impl From<Role> for NewRole {
+    fn from(
+        Role {
+            name, description, ..
+        }: Role,
+    ) -> Self {
+        Self { name, description }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePermission {
+    pub role_id: i32,
+    pub permission_id: i32,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "role_permissions"]
+pub struct NewRolePermission {
+    pub role_id: i32,
+    pub permission_id: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRole {
+    pub user_id: i32,
+    pub role_id: i32,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "user_roles"]
+pub struct NewUserRole {
+    pub user_id: i32,
+    pub role_id: i32,
+}
+
+/// Role definition used for seeding roles on startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleSeed {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
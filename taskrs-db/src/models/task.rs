@@ -0,0 +1,201 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{SmallInt, Varchar};
+use diesel::{Insertable, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::tasks;
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskColumns {
+    Id,
+    ProjectId,
+    Title,
+    Description,
+    Status,
+    Priority,
+    AssigneeId,
+    ReporterId,
+    DueDate,
+    UpdatedAt,
+    CreatedAt,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub id: i32,
+    pub project_id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: TaskPriority,
+    pub assignee_id: Option<i32>,
+    pub reporter_id: Option<i32>,
+    pub due_date: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    /// Set while the task is in the trash with its project
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Task {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<Task> {
+        let new_task: NewTask = self.into();
+        diesel::insert_into(tasks::table)
+            .values(new_task)
+            .get_result(conn)
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "tasks"]
+struct NewTask {
+    pub project_id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
+    pub assignee_id: Option<i32>,
+    pub reporter_id: Option<i32>,
+    pub due_date: Option<NaiveDateTime>,
+}
+
+impl From<Task> for NewTask {
+    fn from(
+        Task {
+            project_id,
+            title,
+            description,
+            status,
+            priority,
+            assignee_id,
+            reporter_id,
+            due_date,
+            ..
+        }: Task,
+    ) -> Self {
+        Self {
+            project_id,
+            title,
+            description,
+            status,
+            priority,
+            assignee_id,
+            reporter_id,
+            due_date,
+        }
+    }
+}
+
+/// Workflow state of a task, stored as text in `tasks.status`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "Varchar"]
+pub enum TaskStatus {
+    #[default]
+    Open,
+    InProgress,
+    Done,
+    Closed,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Open => "open",
+            TaskStatus::InProgress => "in_progress",
+            TaskStatus::Done => "done",
+            TaskStatus::Closed => "closed",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for TaskStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for TaskStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"open" => Ok(TaskStatus::Open),
+            b"in_progress" => Ok(TaskStatus::InProgress),
+            b"done" => Ok(TaskStatus::Done),
+            b"closed" => Ok(TaskStatus::Closed),
+            _ => Err("Unrecognized task status".into()),
+        }
+    }
+}
+
+/// Priority of a task, stored as a number in `tasks.priority` so it can be ordered
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "SmallInt"]
+pub enum TaskPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl ToSql<SmallInt, Pg> for TaskPriority {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let value: i16 = match self {
+            TaskPriority::Low => 0,
+            TaskPriority::Normal => 1,
+            TaskPriority::High => 2,
+            TaskPriority::Urgent => 3,
+        };
+        ToSql::<SmallInt, Pg>::to_sql(&value, out)
+    }
+}
+
+impl FromSql<SmallInt, Pg> for TaskPriority {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <i16 as FromSql<SmallInt, Pg>>::from_sql(bytes)? {
+            0 => Ok(TaskPriority::Low),
+            1 => Ok(TaskPriority::Normal),
+            2 => Ok(TaskPriority::High),
+            3 => Ok(TaskPriority::Urgent),
+            _ => Err("Unrecognized task priority".into()),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{totp_recovery_codes, user_totp};
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct UserTotp {
+    pub user_id: i32,
+    pub secret: String,
+    pub confirmed: bool,
+    pub last_used_step: Option<i64>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl UserTotp {
+    /// Inserts the secret or replaces an existing one, which is then unconfirmed again
+    pub fn upsert(self, conn: &DbConnection) -> diesel::QueryResult<UserTotp> {
+        let new_user_totp: NewUserTotp = self.into();
+        diesel::insert_into(user_totp::table)
+            .values(&new_user_totp)
+            .on_conflict(user_totp::user_id)
+            .do_update()
+            .set((
+                user_totp::secret.eq(&new_user_totp.secret),
+                user_totp::confirmed.eq(false),
+                user_totp::last_used_step.eq(None::<i64>),
+            ))
+            .get_result(conn)
+    }
+
+    pub fn find(user_id: i32, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        user_totp::table
+            .find(user_id)
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    /// Returns the secret of the user if two factor authentication is enabled
+    pub fn find_confirmed(user_id: i32, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        user_totp::table
+            .find(user_id)
+            .filter(user_totp::confirmed.eq(true))
+            .first::<Self>(conn)
+            .optional()
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "user_totp"]
+struct NewUserTotp {
+    pub user_id: i32,
+    pub secret: String,
+}
+
+impl From<UserTotp> for NewUserTotp {
+    fn from(
+        UserTotp {
+            user_id, secret, ..
+        }: UserTotp,
+    ) -> Self {
+        Self { user_id, secret }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpRecoveryCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "totp_recovery_codes"]
+pub struct NewTotpRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+}
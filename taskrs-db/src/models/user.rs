@@ -1,7 +1,10 @@
 use chrono::NaiveDateTime;
-use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use diesel::{
+    AsChangeset, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::models::double_option;
 use crate::schema::users;
 use crate::DbConnection;
 
@@ -10,12 +13,12 @@ use crate::DbConnection;
 pub enum UserColumns {
     Id,
     Email,
-    Password,
     FirstName,
     LastName,
     Activated,
     UpdatedAt,
     CreatedAt,
+    DeletedAt,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
@@ -23,6 +26,8 @@ pub enum UserColumns {
 pub struct User {
     pub id: i32,
     pub email: String,
+    /// Argon2 hash, only read from requests
+    #[serde(skip_serializing)]
     pub password: String,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
@@ -30,6 +35,11 @@ pub struct User {
     pub activated: bool,
     pub updated_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
+    /// Incremented to invalidate all issued access tokens
+    #[serde(default, skip_serializing)]
+    pub token_version: i32,
+    /// Set while the user is in the trash
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 impl User {
@@ -55,12 +65,56 @@ impl User {
             .map(|user| user.is_some())
     }
 
+    /// Increments the token version of the user and returns the new version
+    pub fn bump_token_version(user_id: i32, conn: &DbConnection) -> diesel::QueryResult<i32> {
+        diesel::update(users::table.find(user_id))
+            .set(users::token_version.eq(users::token_version + 1))
+            .returning(users::token_version)
+            .get_result(conn)
+    }
+
+    /// Deactivated and deleted users can not log in
+    pub fn can_login(&self) -> bool {
+        self.activated && self.deleted_at.is_none()
+    }
+
     pub fn hash_password(&mut self) -> argon2::Result<()> {
+        self.password = Self::hash(&self.password)?;
+
+        Ok(())
+    }
+
+    pub fn hash(password: &str) -> argon2::Result<String> {
         let salt = rand::random::<[u8; 16]>();
         let config = argon2::Config::default();
-        self.password = argon2::hash_encoded(self.password.as_bytes(), &salt, &config)?;
+        argon2::hash_encoded(password.as_bytes(), &salt, &config)
+    }
+}
 
-        Ok(())
+/// Changes to a user, missing fields are not updated
+#[derive(Debug, Clone, Default, Serialize, Deserialize, AsChangeset)]
+#[serde(rename_all = "camelCase")]
+#[table_name = "users"]
+pub struct UserChangeset {
+    pub id: i32,
+    pub email: Option<String>,
+    /// Plain text until hashed by the caller
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub first_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub last_name: Option<Option<String>>,
+    pub activated: Option<bool>,
+}
+
+impl UserChangeset {
+    pub fn is_empty(&self) -> bool {
+        self.email.is_none()
+            && self.password.is_none()
+            && self.first_name.is_none()
+            && self.last_name.is_none()
+            && self.activated.is_none()
     }
 }
 
@@ -0,0 +1,64 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::user_identities;
+use crate::DbConnection;
+
+/// Links a user to an account of an external identity provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    pub issuer: String,
+    pub subject: String,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl UserIdentity {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<UserIdentity> {
+        let new_user_identity: NewUserIdentity = self.into();
+        diesel::insert_into(user_identities::table)
+            .values(new_user_identity)
+            .get_result(conn)
+    }
+
+    pub fn find(
+        issuer: &str,
+        subject: &str,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Self>> {
+        user_identities::table
+            .filter(user_identities::issuer.eq(issuer))
+            .filter(user_identities::subject.eq(subject))
+            .first::<Self>(conn)
+            .optional()
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "user_identities"]
+struct NewUserIdentity {
+    pub user_id: i32,
+    pub issuer: String,
+    pub subject: String,
+}
+
+impl From<UserIdentity> for NewUserIdentity {
+    fn from(
+        UserIdentity {
+            user_id,
+            issuer,
+            subject,
+            ..
+        }: UserIdentity,
+    ) -> Self {
+        Self {
+            user_id,
+            issuer,
+            subject,
+        }
+    }
+}
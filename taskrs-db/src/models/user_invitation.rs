@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::user_invitations;
+use crate::DbConnection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInvitation {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub exp: i64,
+    pub invited_by: Option<i32>,
+    pub used_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl UserInvitation {
+    pub fn insert(self, conn: &DbConnection) -> diesel::QueryResult<UserInvitation> {
+        let new_user_invitation: NewUserInvitation = self.into();
+        diesel::insert_into(user_invitations::table)
+            .values(new_user_invitation)
+            .get_result(conn)
+    }
+
+    pub fn find_by_hash(q: &str, conn: &DbConnection) -> diesel::QueryResult<Option<Self>> {
+        user_invitations::table
+            .filter(user_invitations::token_hash.eq(q))
+            .first::<Self>(conn)
+            .optional()
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "user_invitations"]
+struct NewUserInvitation {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub exp: i64,
+    pub invited_by: Option<i32>,
+}
+
+impl From<UserInvitation> for NewUserInvitation {
+    fn from(
+        UserInvitation {
+            user_id,
+            token_hash,
+            exp,
+            invited_by,
+            ..
+        }: UserInvitation,
+    ) -> Self {
+        Self {
+            user_id,
+            token_hash,
+            exp,
+            invited_by,
+        }
+    }
+}
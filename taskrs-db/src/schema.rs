@@ -1,3 +1,25 @@
+table! {
+    api_token_permissions (api_token_id, permission_id) {
+        api_token_id -> Int4,
+        permission_id -> Int4,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    api_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        token_hash -> Varchar,
+        exp -> Nullable<Int8>,
+        last_used_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     auth_refresh_tokens (id) {
         id -> Int4,
@@ -7,6 +29,11 @@ table! {
         exp -> Int8,
         updated_at -> Nullable<Timestamp>,
         created_at -> Nullable<Timestamp>,
+        family -> Varchar,
+        rotated_at -> Nullable<Timestamp>,
+        user_agent -> Nullable<Varchar>,
+        ip -> Nullable<Varchar>,
+        last_used_at -> Nullable<Timestamp>,
     }
 }
 
@@ -17,6 +44,32 @@ table! {
         parent_category_id -> Nullable<Int4>,
         updated_at -> Nullable<Timestamp>,
         created_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    login_failures (id) {
+        id -> Int4,
+        scope -> Varchar,
+        identifier -> Varchar,
+        failures -> Int4,
+        locked_until -> Nullable<Int8>,
+        last_failure -> Int8,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    password_reset_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        exp -> Int8,
+        used_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
     }
 }
 
@@ -51,6 +104,78 @@ table! {
         creator_id -> Nullable<Int4>,
         updated_at -> Nullable<Timestamp>,
         created_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    role_permissions (role_id, permission_id) {
+        role_id -> Int4,
+        permission_id -> Int4,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    roles (id) {
+        id -> Int4,
+        name -> Varchar,
+        description -> Nullable<Varchar>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    tasks (id) {
+        id -> Int4,
+        project_id -> Int4,
+        title -> Varchar,
+        description -> Nullable<Text>,
+        status -> Varchar,
+        priority -> Int2,
+        assignee_id -> Nullable<Int4>,
+        reporter_id -> Nullable<Int4>,
+        due_date -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    totp_recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        issuer -> Varchar,
+        subject -> Varchar,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_invitations (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        exp -> Int8,
+        invited_by -> Nullable<Int4>,
+        used_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
     }
 }
 
@@ -63,6 +188,26 @@ table! {
     }
 }
 
+table! {
+    user_roles (user_id, role_id) {
+        user_id -> Int4,
+        role_id -> Int4,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_totp (user_id) {
+        user_id -> Int4,
+        secret -> Varchar,
+        confirmed -> Bool,
+        last_used_step -> Nullable<Int8>,
+        updated_at -> Nullable<Timestamp>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     users (id) {
         id -> Int4,
@@ -73,20 +218,46 @@ table! {
         activated -> Bool,
         updated_at -> Nullable<Timestamp>,
         created_at -> Nullable<Timestamp>,
+        token_version -> Int4,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
+joinable!(api_token_permissions -> api_tokens (api_token_id));
+joinable!(api_token_permissions -> permissions (permission_id));
+joinable!(api_tokens -> users (user_id));
 joinable!(auth_refresh_tokens -> users (user_id));
+joinable!(password_reset_tokens -> users (user_id));
 joinable!(projects -> categories (category_id));
+joinable!(role_permissions -> permissions (permission_id));
+joinable!(role_permissions -> roles (role_id));
+joinable!(tasks -> projects (project_id));
+joinable!(totp_recovery_codes -> users (user_id));
+joinable!(user_identities -> users (user_id));
 joinable!(user_permissions -> permissions (permission_id));
 joinable!(user_permissions -> users (user_id));
+joinable!(user_roles -> roles (role_id));
+joinable!(user_roles -> users (user_id));
+joinable!(user_totp -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    api_token_permissions,
+    api_tokens,
     auth_refresh_tokens,
     categories,
+    login_failures,
+    password_reset_tokens,
     permissions,
     project_members,
     projects,
+    role_permissions,
+    roles,
+    tasks,
+    totp_recovery_codes,
+    user_identities,
+    user_invitations,
     user_permissions,
+    user_roles,
+    user_totp,
     users,
 );
@@ -0,0 +1,12 @@
+use sha2::{Digest, Sha256};
+
+/// Generates a random token that can be handed out to a user
+pub fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Hashes a token for storing it in the database.
+/// Tokens have enough entropy, so a fast unsalted hash is sufficient and allows lookups.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
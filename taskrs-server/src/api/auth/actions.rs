@@ -1,37 +1,403 @@
+use chrono::Utc;
 use diesel::prelude::*;
 use diesel::{QueryDsl, RunQueryDsl};
 
 use taskrs_db::models::auth_refresh_token::AuthRefreshToken;
+use taskrs_db::models::login_failure::{LoginFailure, SCOPE_EMAIL, SCOPE_IP};
+use taskrs_db::models::password_reset_token::PasswordResetToken;
+use taskrs_db::models::totp::{NewTotpRecoveryCode, UserTotp};
 use taskrs_db::models::user::{SimpleUser, User};
+use taskrs_db::models::user_identity::UserIdentity;
+use taskrs_db::models::user_invitation::UserInvitation;
+use taskrs_db::token::{generate_token, hash_token};
 use taskrs_db::DbConnection;
 
-use crate::models::user_token::{TokenUser, UserRefreshToken, UserToken};
-use crate::CONFIG;
+use crate::mailer::Message;
+use crate::models::user_token::{
+    InvitationToken, OidcState, TokenUser, TotpChallenge, UserRefreshToken, UserToken,
+};
+use crate::oidc::{self, IdTokenClaims};
+use crate::totp;
+use crate::utils::{bump_token_version, invalidate_token_version_cache};
+use crate::{AUTHENTICATORS, CONFIG, JWT_KEYS, MAILER, PASSWORD_POLICY};
 
-use super::UserTokensDto;
+use super::{
+    AcceptInvitationDto, DisableTotpDto, DisableTotpResult, ForgotPasswordDto, LoginResult,
+    OidcCallbackDto, OidcLoginDto, ResetPasswordDto, SessionDto, SessionInfo, SetPasswordResult,
+    TotpChallengeDto, TotpEnrollmentDto, TotpLoginDto, TotpLoginResult, UnlockLoginDto,
+    UserTokensDto,
+};
 
-pub fn login(user: SimpleUser, conn: &DbConnection) -> anyhow::Result<Option<UserTokensDto>> {
-    debug!("Find user with email ' {}'", &user.email);
-    let db_user = match User::find_by_email(&user.email, conn)? {
-        None => return Ok(None),
+pub fn login(
+    user: SimpleUser,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<LoginResult> {
+    // Check if email or IP is locked
+    if let Some(locked_until) = login_locked_until(&user.email, &session, conn)? {
+        debug!("Login is locked until {}", locked_until);
+        return Ok(LoginResult::Locked(locked_until));
+    }
+
+    let db_user = match authenticate(&user.email, &user.password, conn) {
+        None => {
+            return Ok(login_failed(&user.email, &session, conn)?
+                .map_or(LoginResult::Invalid, LoginResult::Locked))
+        }
         Some(user) => user,
     };
 
-    // Check if user is deactivated
-    if !db_user.activated {
-        debug!("User deactivated");
-        return Ok(None);
+    // Check if user is deactivated or deleted
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(login_failed(&user.email, &session, conn)?
+            .map_or(LoginResult::Invalid, LoginResult::Locked));
     }
 
-    let matches = argon2::verify_encoded(&db_user.password, user.password.as_bytes())?;
-    if !matches {
-        debug!("Wrong password");
-        return Ok(None);
+    // Second step is needed if two factor authentication is enabled
+    if UserTotp::find_confirmed(db_user.id, conn)?.is_some() {
+        debug!("TOTP code required");
+        let challenge = JWT_KEYS.encode_internal(&TotpChallenge::new(db_user.id))?;
+        return Ok(LoginResult::TotpRequired(TotpChallengeDto { challenge }));
+    }
+
+    reset_login_failures(&db_user.email, conn)?;
+
+    // Every login starts a new token family
+    let tokens = generate_tokens(db_user.into(), generate_token(), session, conn)?;
+
+    Ok(LoginResult::Ok(tokens))
+}
+
+/// Second login step for users with two factor authentication.
+/// Accepts a TOTP code or an unused recovery code.
+pub fn login_totp(
+    dto: TotpLoginDto,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<TotpLoginResult> {
+    use taskrs_db::schema::{totp_recovery_codes, users};
+
+    // Decode challenge
+    let user_id = match JWT_KEYS.decode_internal::<TotpChallenge>(&dto.challenge) {
+        Err(err) => {
+            debug!("TOTP challenge is invalid: {}", err);
+            return Ok(TotpLoginResult::Invalid);
+        }
+        Ok(data) => data.claims.user_id,
+    };
+
+    let db_user = match users::table.find(user_id).first::<User>(conn).optional()? {
+        None => return Ok(TotpLoginResult::Invalid),
+        Some(user) => user,
+    };
+
+    // Check if email or IP is locked
+    if let Some(locked_until) = login_locked_until(&db_user.email, &session, conn)? {
+        debug!("Login is locked until {}", locked_until);
+        return Ok(TotpLoginResult::Locked(locked_until));
+    }
+
+    // Check if user is deactivated or deleted
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(TotpLoginResult::Invalid);
+    }
+
+    let db_totp = match UserTotp::find_confirmed(user_id, conn)? {
+        None => return Ok(TotpLoginResult::Invalid),
+        Some(totp) => totp,
+    };
+
+    let valid = match totp::verify(
+        &db_totp.secret,
+        &dto.code,
+        Utc::now().timestamp(),
+        db_totp.last_used_step,
+    ) {
+        Some(step) => use_totp_step(user_id, step, conn)?,
+        None => {
+            // Try recovery codes
+            diesel::update(
+                totp_recovery_codes::table
+                    .filter(totp_recovery_codes::user_id.eq(user_id))
+                    .filter(totp_recovery_codes::code_hash.eq(hash_token(dto.code.trim())))
+                    .filter(totp_recovery_codes::used_at.is_null()),
+            )
+            .set(totp_recovery_codes::used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)?
+                > 0
+        }
+    };
+
+    if !valid {
+        debug!("Wrong TOTP code");
+        return Ok(login_failed(&db_user.email, &session, conn)?
+            .map_or(TotpLoginResult::Invalid, TotpLoginResult::Locked));
+    }
+
+    reset_login_failures(&db_user.email, conn)?;
+
+    // Every login starts a new token family
+    let tokens = generate_tokens(db_user.into(), generate_token(), session, conn)?;
+
+    Ok(TotpLoginResult::Ok(tokens))
+}
+
+/// Remembers the time step of a TOTP code so the code can't be used again.
+/// Returns `false` if the step or a later one was already used.
+fn use_totp_step(user_id: i32, step: i64, conn: &DbConnection) -> diesel::QueryResult<bool> {
+    use taskrs_db::schema::user_totp;
+
+    let updated = diesel::update(
+        user_totp::table.find(user_id).filter(
+            user_totp::last_used_step
+                .is_null()
+                .or(user_totp::last_used_step.lt(step)),
+        ),
+    )
+    .set(user_totp::last_used_step.eq(step))
+    .execute(conn)?;
+
+    Ok(updated == 1)
+}
+
+/// Tries the configured authenticators in order until one accepts the credentials
+fn authenticate(email: &str, password: &str, conn: &DbConnection) -> Option<User> {
+    for authenticator in AUTHENTICATORS.iter() {
+        match authenticator.authenticate(email, password, conn) {
+            Ok(Some(user)) => return Some(user),
+            Ok(None) => {}
+            Err(err) => error!("Error while authenticating '{}': {}", email, err),
+        }
+    }
+
+    None
+}
+
+/// Returns the URL of the identity provider the user has to log in at
+/// and the state the callback has to be called with
+pub fn oidc_login_url() -> anyhow::Result<(OidcLoginDto, String)> {
+    let metadata = oidc::discover(&CONFIG.oidc.issuer)?;
+
+    // The nonce is carried in the signed state and checked against the ID token
+    let nonce = generate_token();
+    let state = JWT_KEYS.encode_internal(&OidcState::new(nonce.clone()))?;
+
+    let dto = OidcLoginDto {
+        url: oidc::authorization_url(&metadata, &CONFIG.oidc, &state, &nonce)?,
+    };
+    Ok((dto, state))
+}
+
+/// Completes the login at the identity provider and issues tokens for the linked user.
+/// Users with two factor authentication get a TOTP challenge like with `login`.
+pub fn oidc_login(
+    dto: OidcCallbackDto,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<LoginResult> {
+    let nonce = match JWT_KEYS.decode_internal::<OidcState>(&dto.state) {
+        Err(err) => {
+            debug!("OIDC state is invalid: {}", err);
+            return Ok(LoginResult::Invalid);
+        }
+        Ok(data) => data.claims.nonce,
+    };
+
+    let metadata = oidc::discover(&CONFIG.oidc.issuer)?;
+    let id_token = match oidc::exchange_code(&metadata, &CONFIG.oidc, &dto.code)? {
+        None => return Ok(LoginResult::Invalid),
+        Some(id_token) => id_token,
+    };
+
+    let claims = match oidc::verify_id_token(&metadata, &CONFIG.oidc, &id_token, &nonce) {
+        Err(err) => {
+            warn!("ID token is invalid: {}", err);
+            return Ok(LoginResult::Invalid);
+        }
+        Ok(claims) => claims,
+    };
+
+    let db_user = match oidc_user(&metadata.issuer, claims, conn)? {
+        None => return Ok(LoginResult::Invalid),
+        Some(user) => user,
+    };
+
+    // Check if user is deactivated or deleted
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(LoginResult::Invalid);
+    }
+
+    // The provider does not replace the second factor
+    if UserTotp::find_confirmed(db_user.id, conn)?.is_some() {
+        debug!("TOTP code required");
+        let challenge = JWT_KEYS.encode_internal(&TotpChallenge::new(db_user.id))?;
+        return Ok(LoginResult::TotpRequired(TotpChallengeDto { challenge }));
+    }
+
+    // Every login starts a new token family
+    let tokens = generate_tokens(db_user.into(), generate_token(), session, conn)?;
+
+    Ok(LoginResult::Ok(tokens))
+}
+
+/// Finds the user linked to the identity.
+/// Unknown identities are linked to existing users by verified email or provisioned if enabled.
+fn oidc_user(
+    issuer: &str,
+    claims: IdTokenClaims,
+    conn: &DbConnection,
+) -> anyhow::Result<Option<User>> {
+    use taskrs_db::schema::users;
+
+    if let Some(identity) = UserIdentity::find(issuer, &claims.sub, conn)? {
+        return Ok(users::table
+            .find(identity.user_id)
+            .first::<User>(conn)
+            .optional()?);
+    }
+
+    let email = match claims.email.clone() {
+        Some(email) if claims.email_verified => email,
+        _ => {
+            debug!("Identity {} has no verified email", &claims.sub);
+            return Ok(None);
+        }
+    };
+
+    conn.transaction::<_, anyhow::Error, _>(|| {
+        let user = match User::find_by_email(&email, conn)? {
+            // Otherwise anyone controlling the email at the provider could take over the account
+            Some(user) if CONFIG.oidc.link_existing_users => user,
+            Some(_) => {
+                debug!("Linking existing user '{}' is disabled", &email);
+                return Ok(None);
+            }
+            None if CONFIG.oidc.auto_provision => {
+                info!("Provisioning user '{}' from identity provider", &email);
+                User {
+                    email,
+                    // Random password, the user logs in through the provider
+                    password: User::hash(&generate_token())?,
+                    first_name: claims.given_name,
+                    last_name: claims.family_name,
+                    activated: true,
+                    ..Default::default()
+                }
+                .insert(conn)?
+            }
+            None => {
+                debug!("No user with email '{}'", &email);
+                return Ok(None);
+            }
+        };
+
+        UserIdentity {
+            user_id: user.id,
+            issuer: issuer.to_string(),
+            subject: claims.sub,
+            ..Default::default()
+        }
+        .insert(conn)?;
+
+        Ok(Some(user))
+    })
+}
+
+/// Removes the lock of an email and/or IP
+pub fn unlock_login(dto: UnlockLoginDto, conn: &DbConnection) -> diesel::QueryResult<()> {
+    if let Some(email) = dto.email {
+        debug!("Unlock login for email {}", &email);
+        LoginFailure::delete(SCOPE_EMAIL, &email.to_lowercase(), conn)?;
+    }
+    if let Some(ip) = dto.ip {
+        debug!("Unlock login for IP {}", &ip);
+        LoginFailure::delete(SCOPE_IP, &ip, conn)?;
+    }
+
+    Ok(())
+}
+
+/// Scopes login failures are tracked in and their thresholds
+fn login_failure_scopes(email: &str, session: &SessionInfo) -> Vec<(&'static str, String, u32)> {
+    let protection = &CONFIG.login_protection;
+    let mut scopes = vec![];
+
+    if protection.max_failures > 0 {
+        scopes.push((SCOPE_EMAIL, email.to_lowercase(), protection.max_failures));
+    }
+    if let (true, Some(ip)) = (protection.max_ip_failures > 0, &session.ip) {
+        scopes.push((SCOPE_IP, ip.clone(), protection.max_ip_failures));
+    }
+
+    scopes
+}
+
+/// Returns the end of the lockout if the email or IP is locked
+fn login_locked_until(
+    email: &str,
+    session: &SessionInfo,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<i64>> {
+    let now = Utc::now().timestamp();
+    let mut locked_until = None;
+
+    for (scope, identifier, _) in login_failure_scopes(email, session) {
+        let until = LoginFailure::find(scope, &identifier, conn)?
+            .and_then(|failure| failure.locked_until)
+            .filter(|until| *until > now);
+        locked_until = locked_until.max(until);
+    }
+
+    Ok(locked_until)
+}
+
+/// Records a failed login for the email and IP.
+/// Locks them once the threshold is reached, each further failure doubles the lockout.
+/// Returns the end of the lockout if the email or IP is locked now.
+fn login_failed(
+    email: &str,
+    session: &SessionInfo,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<i64>> {
+    let protection = &CONFIG.login_protection;
+    let now = Utc::now().timestamp();
+    let mut locked_until = None;
+
+    for (scope, identifier, max_failures) in login_failure_scopes(email, session) {
+        let failure = LoginFailure::increment(
+            scope,
+            &identifier,
+            now,
+            protection.failure_window as i64,
+            conn,
+        )?;
+
+        if failure.failures >= max_failures as i32 {
+            let lockout = (protection.lockout_time as i64)
+                .saturating_mul(
+                    2i64.saturating_pow((failure.failures - max_failures as i32) as u32),
+                )
+                .min(protection.max_lockout_time as i64);
+            warn!(
+                "Login for {} {} locked for {} seconds after {} failures",
+                scope, &identifier, lockout, failure.failures
+            );
+            LoginFailure::lock(failure.id, now + lockout, conn)?;
+
+            locked_until = locked_until.max(Some(now + lockout));
+        }
     }
 
-    let tokens = generate_tokens(db_user.into(), conn)?;
+    Ok(locked_until)
+}
+
+fn reset_login_failures(email: &str, conn: &DbConnection) -> diesel::QueryResult<()> {
+    LoginFailure::delete(SCOPE_EMAIL, &email.to_lowercase(), conn)?;
 
-    Ok(Some(tokens))
+    Ok(())
 }
 
 pub fn logout(refresh_token: String, user: TokenUser, conn: &DbConnection) -> anyhow::Result<()> {
@@ -47,7 +413,16 @@ pub fn logout(refresh_token: String, user: TokenUser, conn: &DbConnection) -> an
     Ok(())
 }
 
-pub fn refresh_token(refresh_token: &str, conn: &DbConnection) -> anyhow::Result<Option<String>> {
+/// Exchanges a refresh token for a new pair of tokens.
+/// The old refresh token is rotated and can't be used again. Presenting a rotated token revokes
+/// all tokens of its family, since it was most likely stolen.
+pub fn refresh_token(
+    refresh_token: &str,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<Option<UserTokensDto>> {
+    use taskrs_db::schema::auth_refresh_tokens;
+
     debug!("Find refresh token in database: {}", refresh_token);
     let db_refresh_token = match AuthRefreshToken::find(refresh_token, conn)? {
         None => {
@@ -57,13 +432,15 @@ pub fn refresh_token(refresh_token: &str, conn: &DbConnection) -> anyhow::Result
         Some(token) => token,
     };
 
+    // Check if token was already used
+    if db_refresh_token.rotated_at.is_some() {
+        return revoke_token_family(&db_refresh_token, conn).map(|_| None);
+    }
+
     // Decode Token
-    let user_email = jsonwebtoken::decode::<UserRefreshToken>(
-        &db_refresh_token.token,
-        &jsonwebtoken::DecodingKey::from_secret(CONFIG.refresh_token_secret.as_bytes()),
-        &jsonwebtoken::Validation::default(),
-    )
-    .ok();
+    let user_email = JWT_KEYS
+        .decode_refresh::<UserRefreshToken>(&db_refresh_token.token)
+        .ok();
 
     // Check if token is valid and return None if not
     let user_email = match user_email {
@@ -81,36 +458,69 @@ pub fn refresh_token(refresh_token: &str, conn: &DbConnection) -> anyhow::Result
         Some(user) => user,
     };
 
-    debug!("Create new token");
-    // TODO: Don't recreate EncodingKey everytime
-    let user: TokenUser = db_user.into();
-    let user_token_claim: UserToken = user.into();
-    let access_key = jsonwebtoken::EncodingKey::from_secret(CONFIG.access_token_secret.as_bytes());
-    let access_token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &user_token_claim,
-        &access_key,
-    )?;
+    // Check if user is deactivated or deleted
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(None);
+    }
+
+    conn.transaction::<Option<UserTokensDto>, anyhow::Error, _>(|| {
+        // Rotate token, fails if it was used in the meantime
+        let count = diesel::update(
+            auth_refresh_tokens::table
+                .find(db_refresh_token.id)
+                .filter(auth_refresh_tokens::rotated_at.is_null()),
+        )
+        .set(auth_refresh_tokens::rotated_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+        if count != 1 {
+            return revoke_token_family(&db_refresh_token, conn).map(|_| None);
+        }
+
+        debug!("Create new tokens");
+        let tokens = generate_tokens(
+            db_user.into(),
+            db_refresh_token.family.clone(),
+            session,
+            conn,
+        )?;
+
+        Ok(Some(tokens))
+    })
+}
+
+fn revoke_token_family(
+    db_refresh_token: &AuthRefreshToken,
+    conn: &DbConnection,
+) -> anyhow::Result<()> {
+    use taskrs_db::schema::auth_refresh_tokens;
+
+    warn!(
+        "Reuse of refresh token {} of user {} detected, revoking token family",
+        db_refresh_token.id, db_refresh_token.user_id
+    );
+
+    diesel::delete(
+        auth_refresh_tokens::table.filter(auth_refresh_tokens::family.eq(&db_refresh_token.family)),
+    )
+    .execute(conn)?;
 
-    Ok(Some(access_token))
+    Ok(())
 }
 
-fn generate_tokens(user: TokenUser, conn: &DbConnection) -> anyhow::Result<UserTokensDto> {
+fn generate_tokens(
+    user: TokenUser,
+    family: String,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<UserTokensDto> {
     let user_id = user.id;
-    // TODO: Don't recreate EncodingKey everytime
-    let access_key = jsonwebtoken::EncodingKey::from_secret(CONFIG.access_token_secret.as_bytes());
     let claim: UserToken = user.clone().into();
-    let access_token = jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claim, &access_key)?;
+    let access_token = JWT_KEYS.encode(&claim)?;
 
-    // TODO: Don't recreate EncodingKey everytime
-    let refresh_key =
-        jsonwebtoken::EncodingKey::from_secret(CONFIG.refresh_token_secret.as_bytes());
     let refresh_claim: UserRefreshToken = user.into();
-    let refresh_token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &refresh_claim,
-        &refresh_key,
-    )?;
+    let refresh_token = JWT_KEYS.encode_refresh(&refresh_claim)?;
 
     // Save refresh token in DB
     AuthRefreshToken {
@@ -121,6 +531,11 @@ fn generate_tokens(user: TokenUser, conn: &DbConnection) -> anyhow::Result<UserT
         exp: refresh_claim.exp,
         updated_at: None,
         created_at: None,
+        family,
+        rotated_at: None,
+        user_agent: session.user_agent,
+        ip: session.ip,
+        last_used_at: None,
     }
     .insert(conn)?;
 
@@ -138,3 +553,391 @@ pub fn revoke_token(refresh_token: &str, conn: &DbConnection) -> anyhow::Result<
 
     Ok(())
 }
+
+/// Returns the active sessions of a user
+pub fn get_sessions(user_id: i32, conn: &DbConnection) -> diesel::QueryResult<Vec<SessionDto>> {
+    use taskrs_db::schema::auth_refresh_tokens;
+
+    let tokens = auth_refresh_tokens::table
+        .filter(auth_refresh_tokens::user_id.eq(user_id))
+        .filter(auth_refresh_tokens::rotated_at.is_null())
+        .filter(auth_refresh_tokens::exp.gt(Utc::now().timestamp()))
+        .order(auth_refresh_tokens::last_used_at.desc())
+        .load::<AuthRefreshToken>(conn)?;
+
+    Ok(tokens.into_iter().map(|token| token.into()).collect())
+}
+
+/// Returns the session with the id, used for checking ownership
+pub fn find_session(
+    session_id: i32,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<AuthRefreshToken>> {
+    use taskrs_db::schema::auth_refresh_tokens;
+
+    auth_refresh_tokens::table
+        .find(session_id)
+        .first::<AuthRefreshToken>(conn)
+        .optional()
+}
+
+/// Revokes a session including all previous tokens of it
+pub fn revoke_session(session: &AuthRefreshToken, conn: &DbConnection) -> diesel::QueryResult<()> {
+    use taskrs_db::schema::auth_refresh_tokens;
+    debug!(
+        "Delete refresh token family in database: {}",
+        &session.family
+    );
+
+    diesel::delete(
+        auth_refresh_tokens::table.filter(auth_refresh_tokens::family.eq(&session.family)),
+    )
+    .execute(conn)?;
+
+    Ok(())
+}
+
+/// Revokes all sessions of the user except the one of the refresh token.
+/// Returns `false` if the refresh token does not belong to the user.
+pub fn revoke_other_sessions(
+    refresh_token: &str,
+    user: TokenUser,
+    conn: &DbConnection,
+) -> diesel::QueryResult<bool> {
+    use taskrs_db::schema::auth_refresh_tokens;
+
+    let current = match AuthRefreshToken::find(refresh_token, conn)? {
+        Some(token) if token.user_id == user.id => token,
+        _ => return Ok(false),
+    };
+
+    diesel::delete(
+        auth_refresh_tokens::table
+            .filter(auth_refresh_tokens::user_id.eq(user.id))
+            .filter(auth_refresh_tokens::family.ne(&current.family)),
+    )
+    .execute(conn)?;
+
+    Ok(true)
+}
+
+/// Creates a new unconfirmed TOTP secret for the user.
+/// Returns `None` if two factor authentication is already enabled.
+pub fn enroll_totp(
+    user: TokenUser,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<TotpEnrollmentDto>> {
+    if UserTotp::find_confirmed(user.id, conn)?.is_some() {
+        debug!("TOTP already enabled for user {}", user.id);
+        return Ok(None);
+    }
+
+    let db_totp = UserTotp {
+        user_id: user.id,
+        secret: totp::generate_secret(),
+        ..Default::default()
+    }
+    .upsert(conn)?;
+
+    Ok(Some(TotpEnrollmentDto {
+        provisioning_uri: totp::provisioning_uri(&db_totp.secret, &user.email, &CONFIG.totp_issuer),
+        secret: db_totp.secret,
+    }))
+}
+
+/// Enables two factor authentication if the code matches the enrolled secret.
+/// Returns new recovery codes or `None` if the code is invalid.
+pub fn confirm_totp(
+    user_id: i32,
+    code: &str,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Vec<String>>> {
+    use taskrs_db::schema::{totp_recovery_codes, user_totp};
+
+    let db_totp = match UserTotp::find(user_id, conn)? {
+        Some(totp) if !totp.confirmed => totp,
+        _ => {
+            debug!("No TOTP enrollment for user {}", user_id);
+            return Ok(None);
+        }
+    };
+
+    let step = match totp::verify(&db_totp.secret, code, Utc::now().timestamp(), None) {
+        None => {
+            debug!("Wrong TOTP code");
+            return Ok(None);
+        }
+        Some(step) => step,
+    };
+
+    let recovery_codes = totp::generate_recovery_codes();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(user_totp::table.find(user_id))
+            .set((
+                user_totp::confirmed.eq(true),
+                user_totp::last_used_step.eq(step),
+            ))
+            .execute(conn)?;
+
+        diesel::delete(totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        let new_recovery_codes = recovery_codes
+            .iter()
+            .map(|code| NewTotpRecoveryCode {
+                user_id,
+                code_hash: hash_token(code),
+            })
+            .collect::<Vec<NewTotpRecoveryCode>>();
+
+        diesel::insert_into(totp_recovery_codes::table)
+            .values(&new_recovery_codes)
+            .execute(conn)
+    })?;
+
+    Ok(Some(recovery_codes))
+}
+
+/// Disables two factor authentication. Requires the password of the user or a current TOTP code,
+/// so users without a local password (LDAP, OpenID Connect) can disable it too.
+/// Failed attempts count towards the login lockout.
+pub fn disable_totp(
+    user_id: i32,
+    dto: DisableTotpDto,
+    session: SessionInfo,
+    conn: &DbConnection,
+) -> anyhow::Result<DisableTotpResult> {
+    use taskrs_db::schema::{totp_recovery_codes, user_totp, users};
+
+    let db_user = match users::table.find(user_id).first::<User>(conn).optional()? {
+        None => return Ok(DisableTotpResult::Invalid),
+        Some(user) => user,
+    };
+
+    // Check if email or IP is locked
+    if let Some(locked_until) = login_locked_until(&db_user.email, &session, conn)? {
+        debug!("Login is locked until {}", locked_until);
+        return Ok(DisableTotpResult::Locked(locked_until));
+    }
+
+    // The password is checked by the authenticators, so LDAP passwords work as well
+    let password_matches = match &dto.password {
+        Some(password) => {
+            authenticate(&db_user.email, password, conn).is_some_and(|user| user.id == user_id)
+        }
+        None => false,
+    };
+    let valid = password_matches
+        || match (&dto.code, UserTotp::find_confirmed(user_id, conn)?) {
+            (Some(code), Some(db_totp)) => match totp::verify(
+                &db_totp.secret,
+                code,
+                Utc::now().timestamp(),
+                db_totp.last_used_step,
+            ) {
+                Some(step) => use_totp_step(user_id, step, conn)?,
+                None => false,
+            },
+            _ => false,
+        };
+
+    if !valid {
+        debug!("Wrong password or TOTP code");
+        return Ok(login_failed(&db_user.email, &session, conn)?
+            .map_or(DisableTotpResult::Invalid, DisableTotpResult::Locked));
+    }
+
+    reset_login_failures(&db_user.email, conn)?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::delete(totp_recovery_codes::table.filter(totp_recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(user_totp::table.find(user_id)).execute(conn)
+    })?;
+
+    Ok(DisableTotpResult::Ok)
+}
+
+/// Sends a password reset token to the user.
+/// Does nothing if the user does not exist and only logs failed deliveries, so callers can't probe for emails.
+pub fn forgot_password(dto: ForgotPasswordDto, conn: &DbConnection) -> anyhow::Result<()> {
+    use taskrs_db::schema::password_reset_tokens;
+
+    debug!("Find user with email ' {}'", &dto.email);
+    let db_user = match User::find_by_email(&dto.email, conn)? {
+        None => return Ok(()),
+        Some(user) => user,
+    };
+
+    // Check if user is deactivated or deleted
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(());
+    }
+
+    // Only the latest token is valid
+    diesel::delete(
+        password_reset_tokens::table
+            .filter(password_reset_tokens::user_id.eq(db_user.id))
+            .filter(password_reset_tokens::used_at.is_null()),
+    )
+    .execute(conn)?;
+
+    let token = generate_token();
+    let exp = Utc::now().timestamp() + (CONFIG.password_reset_token_expiration_time as i64);
+    PasswordResetToken {
+        id: 0,
+        user_id: db_user.id,
+        token_hash: hash_token(&token),
+        exp,
+        used_at: None,
+        updated_at: None,
+        created_at: None,
+    }
+    .insert(conn)?;
+
+    // A failed delivery must not reveal that the email is registered
+    if let Err(e) = MAILER.send(&Message {
+        to: db_user.email,
+        subject: "Reset your taskrs password".to_string(),
+        body: format!(
+            "Use the following token to reset your password:\n\n{}\n\nThe token is valid for {} minutes. \
+            If you did not request a password reset, you can ignore this mail.",
+            token,
+            CONFIG.password_reset_token_expiration_time / 60
+        ),
+    }) {
+        error!(
+            "Could not send password reset mail to user {}: {}",
+            db_user.id, e
+        );
+    }
+
+    Ok(())
+}
+
+/// Sets a new password using a reset token.
+/// Fails if the token is unknown, expired or was already used.
+pub fn reset_password(
+    dto: ResetPasswordDto,
+    conn: &DbConnection,
+) -> anyhow::Result<SetPasswordResult> {
+    use taskrs_db::schema::{auth_refresh_tokens, password_reset_tokens, users};
+
+    let db_token = match PasswordResetToken::find_by_hash(&hash_token(&dto.token), conn)? {
+        None => {
+            debug!("Password reset token does not exist");
+            return Ok(SetPasswordResult::InvalidToken);
+        }
+        Some(token) => token,
+    };
+
+    if db_token.exp < Utc::now().timestamp() {
+        debug!("Password reset token is expired");
+        return Ok(SetPasswordResult::InvalidToken);
+    }
+
+    let email = users::table
+        .find(db_token.user_id)
+        .select(users::email)
+        .first::<String>(conn)?;
+    let violations = PASSWORD_POLICY.validate(&dto.password, &email);
+    if !violations.is_empty() {
+        return Ok(SetPasswordResult::PolicyViolated(violations));
+    }
+
+    let password = User::hash(&dto.password)?;
+
+    let result = conn.transaction::<_, anyhow::Error, _>(|| {
+        // Mark token as used, fails if it was used in the meantime
+        let count = diesel::update(
+            password_reset_tokens::table
+                .find(db_token.id)
+                .filter(password_reset_tokens::used_at.is_null()),
+        )
+        .set(password_reset_tokens::used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+        if count != 1 {
+            debug!("Password reset token was already used");
+            return Ok(SetPasswordResult::InvalidToken);
+        }
+
+        diesel::update(users::table.find(db_token.user_id))
+            .set(users::password.eq(password))
+            .execute(conn)?;
+        bump_token_version(db_token.user_id, conn)?;
+
+        // Sign out everywhere
+        diesel::delete(
+            auth_refresh_tokens::table.filter(auth_refresh_tokens::user_id.eq(db_token.user_id)),
+        )
+        .execute(conn)?;
+
+        Ok(SetPasswordResult::Ok)
+    })?;
+
+    invalidate_token_version_cache(db_token.user_id);
+
+    Ok(result)
+}
+
+/// Sets the password of an invited user and activates the account.
+/// Fails if the invitation is invalid, expired or was already accepted.
+pub fn accept_invitation(
+    dto: AcceptInvitationDto,
+    conn: &DbConnection,
+) -> anyhow::Result<SetPasswordResult> {
+    use taskrs_db::schema::{user_invitations, users};
+
+    let user_id = match JWT_KEYS.decode_internal::<InvitationToken>(&dto.token) {
+        Err(err) => {
+            debug!("Invitation token is invalid: {}", err);
+            return Ok(SetPasswordResult::InvalidToken);
+        }
+        Ok(data) => data.claims.user_id,
+    };
+
+    // Only the latest invitation is stored
+    let db_invitation = match UserInvitation::find_by_hash(&hash_token(&dto.token), conn)? {
+        Some(invitation) if invitation.user_id == user_id => invitation,
+        _ => {
+            debug!("Invitation does not exist");
+            return Ok(SetPasswordResult::InvalidToken);
+        }
+    };
+
+    let email = users::table
+        .find(user_id)
+        .select(users::email)
+        .first::<String>(conn)?;
+    let violations = PASSWORD_POLICY.validate(&dto.password, &email);
+    if !violations.is_empty() {
+        return Ok(SetPasswordResult::PolicyViolated(violations));
+    }
+
+    let password = User::hash(&dto.password)?;
+
+    conn.transaction::<_, anyhow::Error, _>(|| {
+        // Mark invitation as used, fails if it was used in the meantime
+        let count = diesel::update(
+            user_invitations::table
+                .find(db_invitation.id)
+                .filter(user_invitations::used_at.is_null()),
+        )
+        .set(user_invitations::used_at.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+        if count != 1 {
+            debug!("Invitation was already accepted");
+            return Ok(SetPasswordResult::InvalidToken);
+        }
+
+        diesel::update(users::table.find(user_id))
+            .set((users::password.eq(password), users::activated.eq(true)))
+            .execute(conn)?;
+
+        Ok(SetPasswordResult::Ok)
+    })
+}
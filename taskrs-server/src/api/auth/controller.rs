@@ -1,28 +1,67 @@
-use actix_web::{post, web, HttpResponse};
+use actix_web::cookie::{Cookie, CookieBuilder, SameSite};
+use actix_web::http::header;
+use actix_web::{get, post, web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::Utc;
 
 use taskrs_db::models::user::SimpleUser;
 use taskrs_db::DbPool;
 
-use crate::models::user_token::TokenUser;
+use crate::models::user_token::{JwtUser, TokenUser};
 use crate::permissions;
 use crate::utils;
+use crate::{CONFIG, JWT_KEYS};
 
-use super::actions;
+use super::{
+    actions, AcceptInvitationDto, DisableTotpDto, DisableTotpResult, ForgotPasswordDto,
+    LoginLockedDto, LoginResult, OidcCallbackDto, ResetPasswordDto, SessionFilter, SessionIdDto,
+    SetPasswordResult, TotpCodeDto, TotpLoginDto, TotpLoginResult, TotpRecoveryCodesDto,
+    UnlockLoginDto,
+};
 
 #[post("/login")]
 pub async fn login(
+    req: HttpRequest,
     user: web::Json<SimpleUser>,
     pool: web::Data<DbPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let user = user.into_inner();
+    let session = (&req).into();
 
     // Login user
-    web::block(move || actions::login(user, &conn))
+    web::block(move || actions::login(user, session, &conn))
         .await
-        .map(|tokens| match tokens {
-            Some(token) => HttpResponse::Ok().json(token),
-            None => HttpResponse::BadRequest().finish(),
+        .map(|result| match result {
+            LoginResult::Ok(tokens) => HttpResponse::Ok().json(tokens),
+            LoginResult::TotpRequired(challenge) => HttpResponse::Ok().json(challenge),
+            LoginResult::Invalid => HttpResponse::BadRequest().finish(),
+            LoginResult::Locked(locked_until) => locked_response(locked_until),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Second login step for users with two factor authentication
+#[post("/login/totp")]
+pub async fn login_totp(
+    req: HttpRequest,
+    dto: web::Json<TotpLoginDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+    let session = (&req).into();
+
+    web::block(move || actions::login_totp(dto, session, &conn))
+        .await
+        .map(|result| match result {
+            TotpLoginResult::Ok(tokens) => HttpResponse::Ok().json(tokens),
+            TotpLoginResult::Locked(locked_until) => locked_response(locked_until),
+            TotpLoginResult::Invalid => HttpResponse::BadRequest().finish(),
         })
         .map_err(|e| {
             error!("{}", e);
@@ -32,6 +71,105 @@ pub async fn login(
         })
 }
 
+/// Name of the cookie binding an OpenID Connect login to the browser that started it
+const OIDC_STATE_COOKIE: &str = "taskrs_oidc_state";
+
+/// Returns the URL of the identity provider to start an OpenID Connect login.
+/// The state is also set as cookie, the callback only accepts it from the same browser.
+#[get("/oidc/login")]
+pub async fn oidc_login() -> Result<HttpResponse, actix_web::Error> {
+    web::block(actions::oidc_login_url)
+        .await
+        .map(|(url, state)| {
+            HttpResponse::Ok()
+                .cookie(oidc_state_cookie(state).finish())
+                .json(url)
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Completes an OpenID Connect login with the `code` and `state` the provider redirected with
+#[post("/oidc/callback")]
+pub async fn oidc_callback(
+    req: HttpRequest,
+    dto: web::Json<OidcCallbackDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+    let session = (&req).into();
+
+    // The state is only used once, a max age of zero removes the cookie
+    let removal = oidc_state_cookie(String::new())
+        .max_age(Default::default())
+        .finish();
+
+    // A state from another browser would log this one in to a foreign account (login CSRF)
+    let state_cookie = req.cookie(OIDC_STATE_COOKIE);
+    if state_cookie.as_ref().map(|cookie| cookie.value()) != Some(dto.state.as_str()) {
+        debug!("OIDC state does not match the cookie");
+        return Ok(HttpResponse::BadRequest().cookie(removal).finish());
+    }
+
+    web::block(move || actions::oidc_login(dto, session, &conn))
+        .await
+        .map(|result| {
+            let mut response = match result {
+                LoginResult::Ok(tokens) => HttpResponse::Ok().json(tokens),
+                LoginResult::TotpRequired(challenge) => HttpResponse::Ok().json(challenge),
+                LoginResult::Invalid => HttpResponse::BadRequest().finish(),
+                LoginResult::Locked(locked_until) => locked_response(locked_until),
+            };
+            if let Err(e) = response.add_cookie(&removal) {
+                error!("{}", e);
+            }
+            response
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Unlocks an email and/or IP that was locked after failed logins
+///
+/// Permission: `auth_unlock_login`
+#[post("/unlock")]
+pub async fn unlock_login(
+    dto: web::Json<UnlockLoginDto>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::AUTH_UNLOCK_LOGIN, &conn)?;
+
+    web::block(move || actions::unlock_login(dto, &conn))
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Returns the public keys that access tokens are signed with
+#[get("/jwks")]
+pub async fn jwks() -> HttpResponse {
+    HttpResponse::Ok().json(JWT_KEYS.jwks())
+}
+
 #[post("/logout")]
 pub async fn logout(
     ref_token: web::Json<String>,
@@ -53,18 +191,21 @@ pub async fn logout(
         })
 }
 
+/// Returns a new access and refresh token. The used refresh token becomes invalid.
 #[post("/token")]
 pub async fn refresh_token(
+    req: HttpRequest,
     ref_token: web::Json<String>,
     pool: web::Data<DbPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let ref_token = ref_token.into_inner();
+    let session = (&req).into();
 
-    web::block(move || actions::refresh_token(&ref_token, &conn))
+    web::block(move || actions::refresh_token(&ref_token, session, &conn))
         .await
-        .map(|token| match token {
-            Some(token) => HttpResponse::Ok().json(token),
+        .map(|tokens| match tokens {
+            Some(tokens) => HttpResponse::Ok().json(tokens),
             None => HttpResponse::Forbidden().finish(),
         })
         .map_err(|e| {
@@ -96,3 +237,257 @@ pub async fn revoke_token(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Returns the active sessions of the current user or of the user in `userId`
+///
+/// Permission: `auth_revoke_refresh_token` for sessions of other users
+#[get("/sessions")]
+pub async fn all_sessions(
+    user: JwtUser,
+    filter: web::Query<SessionFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let user_id = filter.into_inner().user_id.unwrap_or(user.id);
+
+    // Check permission
+    if user_id != user.id {
+        utils::has_permission(&user, &permissions::AUTH_REVOKE_REFRESH_TOKEN, &conn)?;
+    }
+
+    web::block(move || actions::get_sessions(user_id, &conn))
+        .await
+        .map(|sessions| HttpResponse::Ok().json(sessions))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Revokes a session by its id
+///
+/// Permission: `auth_revoke_refresh_token` for sessions of other users
+#[post("/sessions/revoke")]
+pub async fn revoke_session(
+    session: web::Json<SessionIdDto>,
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let session_id = session.into_inner().id;
+
+    // Check permission
+    let may_revoke_others =
+        utils::user_has_permission(&user, &permissions::AUTH_REVOKE_REFRESH_TOKEN, &conn)?;
+
+    // Sessions of other users are not found without the permission, so their ids can't be probed
+    let session = actions::find_session(session_id, &conn)
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        })?
+        .filter(|session| session.user_id == user.id || may_revoke_others)
+        .ok_or_else(|| HttpResponse::NotFound().finish())?;
+
+    web::block(move || actions::revoke_session(&session, &conn))
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Revokes all sessions of the current user except the one of the given refresh token
+#[post("/sessions/revoke/others")]
+pub async fn revoke_other_sessions(
+    ref_token: web::Json<String>,
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let ref_token = ref_token.into_inner();
+
+    web::block(move || actions::revoke_other_sessions(&ref_token, user.0, &conn))
+        .await
+        .map(|revoked| match revoked {
+            true => HttpResponse::Ok().finish(),
+            false => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Starts enabling two factor authentication for the current user.
+/// Returns the secret and provisioning URI for authenticator apps.
+#[post("/totp/enroll")]
+pub async fn enroll_totp(
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+
+    web::block(move || actions::enroll_totp(user.0, &conn))
+        .await
+        .map(|enrollment| match enrollment {
+            Some(enrollment) => HttpResponse::Ok().json(enrollment),
+            None => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Enables two factor authentication with a code from the enrolled secret.
+/// Returns the recovery codes, they are only shown once.
+#[post("/totp/confirm")]
+pub async fn confirm_totp(
+    dto: web::Json<TotpCodeDto>,
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    web::block(move || actions::confirm_totp(user.id, &dto.code, &conn))
+        .await
+        .map(|recovery_codes| match recovery_codes {
+            Some(recovery_codes) => {
+                HttpResponse::Ok().json(TotpRecoveryCodesDto { recovery_codes })
+            }
+            None => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Disables two factor authentication for the current user. Requires the password or a TOTP code.
+#[post("/totp/disable")]
+pub async fn disable_totp(
+    req: HttpRequest,
+    dto: web::Json<DisableTotpDto>,
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+    let session = (&req).into();
+
+    web::block(move || actions::disable_totp(user.id, dto, session, &conn))
+        .await
+        .map(|result| match result {
+            DisableTotpResult::Ok => HttpResponse::Ok().finish(),
+            DisableTotpResult::Invalid => HttpResponse::BadRequest().finish(),
+            DisableTotpResult::Locked(locked_until) => locked_response(locked_until),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Sends a password reset token to the user with the email.
+/// Always succeeds, so it can't be used to find registered emails.
+#[post("/password/forgot")]
+pub async fn forgot_password(
+    dto: web::Json<ForgotPasswordDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    web::block(move || actions::forgot_password(dto, &conn))
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Sets a new password using a token from `/password/forgot`
+#[post("/password/reset")]
+pub async fn reset_password(
+    dto: web::Json<ResetPasswordDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    web::block(move || actions::reset_password(dto, &conn))
+        .await
+        .map(|result| match result {
+            SetPasswordResult::Ok => HttpResponse::Ok().finish(),
+            SetPasswordResult::InvalidToken => HttpResponse::BadRequest().finish(),
+            SetPasswordResult::PolicyViolated(violations) => {
+                HttpResponse::BadRequest().json(violations)
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Sets the password of an invited user and activates the account
+#[post("/invitation/accept")]
+pub async fn accept_invitation(
+    dto: web::Json<AcceptInvitationDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    web::block(move || actions::accept_invitation(dto, &conn))
+        .await
+        .map(|result| match result {
+            SetPasswordResult::Ok => HttpResponse::Ok().finish(),
+            SetPasswordResult::InvalidToken => HttpResponse::BadRequest().finish(),
+            SetPasswordResult::PolicyViolated(violations) => {
+                HttpResponse::BadRequest().json(violations)
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Response for locked logins, tells the client when to retry
+fn oidc_state_cookie(state: String) -> CookieBuilder<'static> {
+    Cookie::build(OIDC_STATE_COOKIE, state)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(CONFIG.oidc.redirect_uri.starts_with("https://"))
+}
+
+fn locked_response(locked_until: i64) -> HttpResponse {
+    let retry_after = (locked_until - Utc::now().timestamp()).max(0);
+    HttpResponse::TooManyRequests()
+        .set_header(header::RETRY_AFTER, retry_after.to_string())
+        .json(LoginLockedDto { locked_until })
+}
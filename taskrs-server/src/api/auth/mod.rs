@@ -1,20 +1,43 @@
-use actix_web::{web, Scope};
+use actix_web::{web, HttpRequest, Scope};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+use taskrs_db::models::auth_refresh_token::AuthRefreshToken;
+
+use crate::password_policy::PasswordViolation;
+use crate::utils;
+use crate::CONFIG;
+
 mod actions;
 mod controller;
 
 pub fn register(scope: Scope) -> Scope {
     let mut auth_scope = web::scope("auth");
 
-    // Debug routes
-    if cfg!(debug_assertions) {}
-
     auth_scope = auth_scope
         .service(controller::login)
+        .service(controller::login_totp)
+        .service(controller::unlock_login)
+        .service(controller::jwks)
         .service(controller::logout)
         .service(controller::refresh_token)
-        .service(controller::revoke_token);
+        .service(controller::revoke_token)
+        .service(controller::forgot_password)
+        .service(controller::reset_password)
+        .service(controller::accept_invitation)
+        .service(controller::all_sessions)
+        .service(controller::revoke_session)
+        .service(controller::revoke_other_sessions)
+        .service(controller::enroll_totp)
+        .service(controller::confirm_totp)
+        .service(controller::disable_totp);
+
+    // OpenID Connect routes
+    if !CONFIG.oidc.issuer.is_empty() {
+        auth_scope = auth_scope
+            .service(controller::oidc_login)
+            .service(controller::oidc_callback);
+    }
 
     scope.service(auth_scope)
 }
@@ -25,3 +48,191 @@ pub struct UserTokensDto {
     pub access_token: String,
     pub refresh_token: String,
 }
+
+pub enum LoginResult {
+    Ok(UserTokensDto),
+    TotpRequired(TotpChallengeDto),
+    Invalid,
+    /// Too many failed logins, contains the end of the lockout
+    Locked(i64),
+}
+
+pub enum TotpLoginResult {
+    Ok(UserTokensDto),
+    Invalid,
+    /// Too many failed logins, contains the end of the lockout
+    Locked(i64),
+}
+
+pub enum DisableTotpResult {
+    Ok,
+    Invalid,
+    /// Too many failed attempts, contains the end of the lockout
+    Locked(i64),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginLockedDto {
+    pub locked_until: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockLoginDto {
+    pub email: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpChallengeDto {
+    pub challenge: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpLoginDto {
+    pub challenge: String,
+    /// TOTP or recovery code
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollmentDto {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpCodeDto {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpRecoveryCodesDto {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisableTotpDto {
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Alternative for users without a local password
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgotPasswordDto {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordDto {
+    pub token: String,
+    pub password: String,
+}
+
+pub enum SetPasswordResult {
+    Ok,
+    InvalidToken,
+    PolicyViolated(Vec<PasswordViolation>),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptInvitationDto {
+    pub token: String,
+    pub password: String,
+}
+
+/// Client information stored with a refresh token
+#[derive(Debug, Clone, Default)]
+pub struct SessionInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl From<&HttpRequest> for SessionInfo {
+    fn from(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(|user_agent| user_agent.chars().take(512).collect());
+
+        let ip = utils::client_ip(req).map(|ip| ip.to_string());
+
+        SessionInfo { user_agent, ip }
+    }
+}
+
+/// Active refresh token of a user without the token itself
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDto {
+    pub id: i32,
+    pub user_id: i32,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub exp: i64,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl From<AuthRefreshToken> for SessionDto {
+    fn from(
+        AuthRefreshToken {
+            id,
+            user_id,
+            user_agent,
+            ip,
+            exp,
+            last_used_at,
+            created_at,
+            ..
+        }: AuthRefreshToken,
+    ) -> Self {
+        SessionDto {
+            id,
+            user_id,
+            user_agent,
+            ip,
+            exp,
+            last_used_at,
+            created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFilter {
+    pub user_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionIdDto {
+    pub id: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcLoginDto {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcCallbackDto {
+    pub code: String,
+    pub state: String,
+}
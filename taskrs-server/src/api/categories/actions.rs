@@ -1,12 +1,19 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
 use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer};
 
 use diesel_pagination::{LoadPaginated, PaginationPage};
-use taskrs_db::models::category::{Category, CategoryColumns};
+use taskrs_db::models::category::{Category, CategoryChangeset, CategoryColumns};
 use taskrs_db::{Db, DbConnection};
 
-use crate::api::categories::SubCategoryFilter;
+use crate::api::categories::{
+    CategoryTreeFilter, CategoryTreeNode, SubCategoryFilter, UpdateCategoryResult,
+};
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult, RestoreEntityParams};
 use crate::models::request_filter::{Order, RequestFilter};
 
 pub fn get_all_categories(
@@ -27,6 +34,11 @@ pub fn get_all_categories(
         );
     }
 
+    // Deleted categories are only listed on request
+    if !filter.include_deleted.unwrap_or(false) {
+        db_query = db_query.filter(categories::deleted_at.is_null());
+    }
+
     // Order by
     let order_by = filter.order_by.unwrap_or(CategoryColumns::Name);
     let order = filter.order.unwrap_or(Order::Ascending);
@@ -44,6 +56,9 @@ pub fn get_all_categories(
             CategoryColumns::CreatedAt => {
                 db_query.order((categories::created_at.asc(), categories::id.asc()))
             }
+            CategoryColumns::DeletedAt => {
+                db_query.order((categories::deleted_at.asc(), categories::id.asc()))
+            }
         },
         Order::Descending => match order_by {
             CategoryColumns::Id => db_query.order(categories::id.desc()),
@@ -59,6 +74,9 @@ pub fn get_all_categories(
             CategoryColumns::CreatedAt => {
                 db_query.order((categories::created_at.desc(), categories::id.asc()))
             }
+            CategoryColumns::DeletedAt => {
+                db_query.order((categories::deleted_at.desc(), categories::id.asc()))
+            }
         },
     };
 
@@ -71,9 +89,101 @@ pub fn sub_categories(
 ) -> diesel::QueryResult<Vec<Category>> {
     use taskrs_db::schema::categories;
 
-    categories::table
+    let mut db_query = categories::table
         .filter(categories::parent_category_id.eq(filter.id))
-        .load(conn)
+        .into_boxed::<Db>();
+
+    if !filter.include_deleted.unwrap_or(false) {
+        db_query = db_query.filter(categories::deleted_at.is_null());
+    }
+
+    db_query.load(conn)
+}
+
+/// Returns all categories nested below their parents.
+/// Categories below a deleted category are only listed with `include_deleted`.
+pub fn category_tree(
+    filter: CategoryTreeFilter,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Vec<CategoryTreeNode>> {
+    let categories: Vec<Category> = diesel::sql_query(
+        "WITH RECURSIVE tree AS ( \
+            SELECT * FROM categories \
+            WHERE parent_category_id IS NULL AND ($1 OR deleted_at IS NULL) \
+            UNION \
+            SELECT c.* FROM categories c \
+            JOIN tree t ON c.parent_category_id = t.id \
+            WHERE $1 OR c.deleted_at IS NULL \
+        ) \
+        SELECT * FROM tree ORDER BY name, id",
+    )
+    .bind::<Bool, _>(filter.include_deleted.unwrap_or(false))
+    .load(conn)?;
+
+    let mut children: HashMap<Option<i32>, Vec<Category>> = HashMap::new();
+    for category in categories {
+        children
+            .entry(category.parent_category_id)
+            .or_default()
+            .push(category);
+    }
+
+    Ok(build_tree(None, &mut children))
+}
+
+/// Returns the parents of a category, starting at the root.
+/// Returns `None` if the category does not exist.
+pub fn category_ancestors(
+    category_id: i32,
+    filter: CategoryTreeFilter,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Vec<Category>>> {
+    if !category_exists(category_id, filter.include_deleted, conn)? {
+        return Ok(None);
+    }
+
+    // Updates reject cycles, the path only guards against rows written before that
+    diesel::sql_query(
+        "WITH RECURSIVE ancestors AS ( \
+            SELECT *, 0 AS depth, ARRAY[id] AS path FROM categories WHERE id = $1 \
+            UNION ALL \
+            SELECT c.*, a.depth + 1, a.path || c.id FROM categories c \
+            JOIN ancestors a ON c.id = a.parent_category_id \
+            WHERE c.id <> ALL(a.path) AND ($2 OR c.deleted_at IS NULL) \
+        ) \
+        SELECT * FROM ancestors WHERE depth > 0 ORDER BY depth DESC",
+    )
+    .bind::<Integer, _>(category_id)
+    .bind::<Bool, _>(filter.include_deleted.unwrap_or(false))
+    .load(conn)
+    .map(Some)
+}
+
+/// Returns all sub categories of a category, level by level.
+/// Returns `None` if the category does not exist.
+pub fn category_descendants(
+    category_id: i32,
+    filter: CategoryTreeFilter,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Vec<Category>>> {
+    if !category_exists(category_id, filter.include_deleted, conn)? {
+        return Ok(None);
+    }
+
+    diesel::sql_query(
+        "WITH RECURSIVE descendants AS ( \
+            SELECT *, 0 AS depth, ARRAY[id] AS path FROM categories WHERE id = $1 \
+            UNION ALL \
+            SELECT c.*, d.depth + 1, d.path || c.id FROM categories c \
+            JOIN descendants d ON c.parent_category_id = d.id \
+            WHERE c.id <> ALL(d.path) AND ($2 OR c.deleted_at IS NULL) \
+        ) \
+        SELECT * FROM descendants WHERE depth > 0 ORDER BY depth, name, id",
+    )
+    .bind::<Integer, _>(category_id)
+    .bind::<Bool, _>(filter.include_deleted.unwrap_or(false))
+    .load(conn)
+    .map(Some)
 }
 
 pub fn create_category(
@@ -88,6 +198,7 @@ pub fn create_category(
     Ok(CreateEntityResult::Ok(category.insert(conn)?))
 }
 
+/// Moves a category to the trash, with `cascade` including its sub categories
 pub fn delete_category(
     params: DeleteEntityParams,
     conn: &DbConnection,
@@ -100,13 +211,14 @@ pub fn delete_category(
         } else {
             let sub_categories: Vec<Category> = categories::table
                 .filter(categories::parent_category_id.eq(params.id))
+                .filter(categories::deleted_at.is_null())
                 .load(conn)?;
 
             if !sub_categories.is_empty() {
                 return Ok(DeleteEntityResult::Referenced(sub_categories));
             }
 
-            diesel::delete(categories::table.filter(categories::id.eq(params.id))).execute(conn)?
+            soft_delete_category(params.id, conn)?
         };
 
         if count > 0 {
@@ -117,35 +229,192 @@ pub fn delete_category(
     })
 }
 
+/// Restores a category and its deleted parents from the trash
+pub fn restore_category(
+    params: RestoreEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Category>> {
+    use taskrs_db::schema::categories;
+
+    conn.transaction::<Option<Category>, diesel::result::Error, _>(|| {
+        let target = categories::table
+            .find(params.id)
+            .filter(categories::deleted_at.is_not_null());
+        let category = diesel::update(target)
+            .set(categories::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result::<Category>(conn)
+            .optional()?;
+
+        // A restored category must not hang below a deleted one
+        let mut parent_id = category.as_ref().and_then(|c| c.parent_category_id);
+        while let Some(id) = parent_id {
+            let target = categories::table
+                .find(id)
+                .filter(categories::deleted_at.is_not_null());
+            parent_id = diesel::update(target)
+                .set(categories::deleted_at.eq(None::<NaiveDateTime>))
+                .returning(categories::parent_category_id)
+                .get_result::<Option<i32>>(conn)
+                .optional()?
+                .flatten();
+        }
+
+        Ok(category)
+    })
+}
+
 pub fn update_category(
     category: Category,
     conn: &DbConnection,
-) -> diesel::QueryResult<Option<Category>> {
+) -> diesel::QueryResult<UpdateCategoryResult> {
     use taskrs_db::schema::categories;
 
-    let target = categories::table.find(category.id);
-    diesel::update(target)
-        .set((
-            categories::name.eq(category.name),
-            categories::parent_category_id.eq(category.parent_category_id),
-        ))
-        .get_result::<Category>(conn)
-        .optional()
+    conn.transaction::<UpdateCategoryResult, diesel::result::Error, _>(|| {
+        if let Some(parent_id) = category.parent_category_id {
+            if is_in_subtree(category.id, parent_id, conn)? {
+                return Ok(UpdateCategoryResult::InvalidParent);
+            }
+        }
+
+        let target = categories::table
+            .find(category.id)
+            .filter(categories::deleted_at.is_null());
+        let category = diesel::update(target)
+            .set((
+                categories::name.eq(category.name),
+                categories::parent_category_id.eq(category.parent_category_id),
+            ))
+            .get_result::<Category>(conn)
+            .optional()?;
+
+        Ok(category.map_or(UpdateCategoryResult::NotFound, UpdateCategoryResult::Ok))
+    })
 }
 
+/// Updates the supplied fields of a category
+pub fn patch_category(
+    changes: CategoryChangeset,
+    conn: &DbConnection,
+) -> diesel::QueryResult<UpdateCategoryResult> {
+    use taskrs_db::schema::categories;
+
+    conn.transaction::<UpdateCategoryResult, diesel::result::Error, _>(|| {
+        if let Some(Some(parent_id)) = changes.parent_category_id {
+            if is_in_subtree(changes.id, parent_id, conn)? {
+                return Ok(UpdateCategoryResult::InvalidParent);
+            }
+        }
+
+        let target = categories::table
+            .find(changes.id)
+            .filter(categories::deleted_at.is_null());
+        let category = if changes.is_empty() {
+            target.first::<Category>(conn).optional()?
+        } else {
+            diesel::update(target)
+                .set(&changes)
+                .get_result::<Category>(conn)
+                .optional()?
+        };
+
+        Ok(category.map_or(UpdateCategoryResult::NotFound, UpdateCategoryResult::Ok))
+    })
+}
+
+/// Checks if `parent_id` is the category itself or one of its sub categories,
+/// so moving the category below it would create a cycle.
+/// Locks the table until the end of the transaction, so concurrent moves can not create one either.
+fn is_in_subtree(
+    category_id: i32,
+    parent_id: i32,
+    conn: &DbConnection,
+) -> diesel::QueryResult<bool> {
+    if category_id == parent_id {
+        return Ok(true);
+    }
+
+    diesel::sql_query("LOCK TABLE categories IN SHARE ROW EXCLUSIVE MODE").execute(conn)?;
+
+    // Deleted sub categories count as well, they return with their parent
+    diesel::sql_query(
+        "WITH RECURSIVE descendants AS ( \
+            SELECT *, ARRAY[id] AS path FROM categories WHERE id = $1 \
+            UNION ALL \
+            SELECT c.*, d.path || c.id FROM categories c \
+            JOIN descendants d ON c.parent_category_id = d.id \
+            WHERE c.id <> ALL(d.path) \
+        ) \
+        SELECT * FROM descendants WHERE id = $2 LIMIT 1",
+    )
+    .bind::<Integer, _>(category_id)
+    .bind::<Integer, _>(parent_id)
+    .load::<Category>(conn)
+    .map(|categories| !categories.is_empty())
+}
+
+/// Moves a category and all of its sub categories to the trash in one statement
 fn delete_category_with_dependencies(
     category_id: i32,
     conn: &DbConnection,
 ) -> diesel::QueryResult<usize> {
+    diesel::sql_query(
+        "WITH RECURSIVE tree AS ( \
+            SELECT id FROM categories WHERE id = $1 AND deleted_at IS NULL \
+            UNION \
+            SELECT c.id FROM categories c \
+            JOIN tree t ON c.parent_category_id = t.id \
+            WHERE c.deleted_at IS NULL \
+        ) \
+        UPDATE categories SET deleted_at = now() WHERE id IN (SELECT id FROM tree)",
+    )
+    .bind::<Integer, _>(category_id)
+    .execute(conn)
+}
+
+fn category_exists(
+    category_id: i32,
+    include_deleted: Option<bool>,
+    conn: &DbConnection,
+) -> diesel::QueryResult<bool> {
     use taskrs_db::schema::categories;
 
-    let sub_categories: Vec<Category> = categories::table
-        .filter(categories::parent_category_id.eq(category_id))
-        .load(conn)?;
+    let mut db_query = categories::table
+        .find(category_id)
+        .select(categories::id)
+        .into_boxed::<Db>();
 
-    for sub_category in sub_categories {
-        delete_category_with_dependencies(sub_category.id, conn)?;
+    if !include_deleted.unwrap_or(false) {
+        db_query = db_query.filter(categories::deleted_at.is_null());
     }
 
-    diesel::delete(categories::table.filter(categories::id.eq(category_id))).execute(conn)
+    db_query
+        .first::<i32>(conn)
+        .optional()
+        .map(|id| id.is_some())
+}
+
+fn build_tree(
+    parent_id: Option<i32>,
+    children: &mut HashMap<Option<i32>, Vec<Category>>,
+) -> Vec<CategoryTreeNode> {
+    children
+        .remove(&parent_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|category| {
+            let children = build_tree(Some(category.id), children);
+            CategoryTreeNode { category, children }
+        })
+        .collect()
+}
+
+fn soft_delete_category(category_id: i32, conn: &DbConnection) -> diesel::QueryResult<usize> {
+    use taskrs_db::schema::categories;
+
+    let target = categories::table
+        .find(category_id)
+        .filter(categories::deleted_at.is_null());
+    diesel::update(target)
+        .set(categories::deleted_at.eq(now.nullable()))
+        .execute(conn)
 }
@@ -1,11 +1,12 @@
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse};
 
-use taskrs_db::models::category::{Category, CategoryColumns};
+use taskrs_db::models::category::{Category, CategoryChangeset, CategoryColumns};
 use taskrs_db::DbPool;
 
-use crate::api::categories::SubCategoryFilter;
+use crate::api::categories::{CategoryTreeFilter, SubCategoryFilter, UpdateCategoryResult};
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult, RestoreEntityParams};
+use crate::models::entity_version::{self, IfMatch, IfMatchResult};
 use crate::models::request_filter::RequestFilter;
 use crate::models::user_token::TokenUser;
 use crate::permissions;
@@ -63,6 +64,94 @@ pub async fn sub_categories(
         })
 }
 
+/// Returns all categories nested below their parents
+///
+/// Permission: `category_get_all`
+#[get("/tree")]
+pub async fn category_tree(
+    user: TokenUser,
+    filter: web::Query<CategoryTreeFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::CATEGORY_GET_ALL, &conn)?;
+
+    web::block(move || actions::category_tree(filter, &conn))
+        .await
+        .map(|tree| HttpResponse::Ok().json(tree))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Returns the parents of a category, starting at the root
+///
+/// Permission: `category_get_all`
+#[get("/{id}/ancestors")]
+pub async fn category_ancestors(
+    user: TokenUser,
+    category_id: web::Path<i32>,
+    filter: web::Query<CategoryTreeFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let category_id = category_id.into_inner();
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::CATEGORY_GET_ALL, &conn)?;
+
+    web::block(move || actions::category_ancestors(category_id, filter, &conn))
+        .await
+        .map(|categories| match categories {
+            Some(categories) => HttpResponse::Ok().json(categories),
+            None => HttpResponse::NotFound().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Returns all sub categories of a category
+///
+/// Permission: `category_get_all`
+#[get("/{id}/descendants")]
+pub async fn category_descendants(
+    user: TokenUser,
+    category_id: web::Path<i32>,
+    filter: web::Query<CategoryTreeFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let category_id = category_id.into_inner();
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::CATEGORY_GET_ALL, &conn)?;
+
+    web::block(move || actions::category_descendants(category_id, filter, &conn))
+        .await
+        .map(|categories| match categories {
+            Some(categories) => HttpResponse::Ok().json(categories),
+            None => HttpResponse::NotFound().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
 /// Creates a new category
 ///
 /// Permission: `category_create`
@@ -101,6 +190,7 @@ pub async fn delete_category(
     params: web::Query<DeleteEntityParams>,
     user: TokenUser,
     pool: web::Data<DbPool>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let params = params.into_inner();
@@ -109,14 +199,49 @@ pub async fn delete_category(
     utils::has_permission(&user, &permissions::CATEGORY_DELETE, &conn)?;
 
     // Delete category
-    web::block(move || actions::delete_category(params, &conn))
+    web::block(move || {
+        entity_version::if_match::<Category, _, _, _>(params.id, &if_match, &conn, || {
+            actions::delete_category(params, &conn)
+        })
+    })
+    .await
+    .map(|result| match result {
+        IfMatchResult::Ok(DeleteEntityResult::Ok) => HttpResponse::Ok().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::Referenced(references)) => {
+            HttpResponse::BadRequest().json(references)
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Restore a category from the trash
+///
+/// Permission: `category_restore`
+#[post("/restore")]
+pub async fn restore_category(
+    params: web::Query<RestoreEntityParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::CATEGORY_RESTORE, &conn)?;
+
+    // Restore category
+    web::block(move || actions::restore_category(params, &conn))
         .await
-        .map(|result| match result {
-            DeleteEntityResult::Ok => HttpResponse::Ok().finish(),
-            DeleteEntityResult::NotFound => HttpResponse::NotFound().finish(),
-            DeleteEntityResult::Referenced(references) => {
-                HttpResponse::BadRequest().json(references)
-            }
+        .map(|restored_category| match restored_category {
+            Some(category) => entity_version::versioned_response(category),
+            None => HttpResponse::NotFound().finish(),
         })
         .map_err(|e| {
             error!("{}", e);
@@ -134,6 +259,7 @@ pub async fn update_category(
     category: web::Json<Category>,
     user: TokenUser,
     pool: web::Data<DbPool>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let category = category.into_inner();
@@ -142,16 +268,67 @@ pub async fn update_category(
     utils::has_permission(&user, &permissions::CATEGORY_UPDATE, &conn)?;
 
     // Update category
-    web::block(move || actions::update_category(category, &conn))
-        .await
-        .map(|updated_category| match updated_category {
-            Some(category) => HttpResponse::Ok().json(category),
-            None => HttpResponse::NotFound().finish(),
+    web::block(move || {
+        entity_version::if_match::<Category, _, _, _>(category.id, &if_match, &conn, || {
+            actions::update_category(category, &conn)
         })
-        .map_err(|e| {
-            error!("{}", e);
-            HttpResponse::InternalServerError()
-                .body(e.to_string())
-                .into()
+    })
+    .await
+    .map(|updated_category| match updated_category {
+        IfMatchResult::Ok(UpdateCategoryResult::Ok(category)) => {
+            entity_version::versioned_response(category)
+        }
+        IfMatchResult::Ok(UpdateCategoryResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(UpdateCategoryResult::InvalidParent) => {
+            HttpResponse::BadRequest().body("Category can not be moved below itself")
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Update the supplied fields of a category
+///
+/// Permission: `category_update`
+#[patch("")]
+pub async fn patch_category(
+    category: web::Json<CategoryChangeset>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let category = category.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::CATEGORY_UPDATE, &conn)?;
+
+    // Update category
+    web::block(move || {
+        entity_version::if_match::<Category, _, _, _>(category.id, &if_match, &conn, || {
+            actions::patch_category(category, &conn)
         })
+    })
+    .await
+    .map(|updated_category| match updated_category {
+        IfMatchResult::Ok(UpdateCategoryResult::Ok(category)) => {
+            entity_version::versioned_response(category)
+        }
+        IfMatchResult::Ok(UpdateCategoryResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(UpdateCategoryResult::InvalidParent) => {
+            HttpResponse::BadRequest().body("Category can not be moved below itself")
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
 }
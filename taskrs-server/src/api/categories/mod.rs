@@ -1,21 +1,25 @@
 use actix_web::{web, Scope};
 use serde::{Deserialize, Serialize};
 
+use taskrs_db::models::category::Category;
+
 mod actions;
 mod controller;
 
 pub fn register(scope: Scope) -> Scope {
     let mut category_scope = web::scope("categories").wrap(crate::middleware::auth::Authentication);
 
-    // Debug routes
-    if cfg!(debug_assertions) {}
-
     category_scope = category_scope
         .service(controller::all_categories)
         .service(controller::sub_categories)
+        .service(controller::category_tree)
+        .service(controller::category_ancestors)
+        .service(controller::category_descendants)
         .service(controller::create_category)
         .service(controller::delete_category)
-        .service(controller::update_category);
+        .service(controller::restore_category)
+        .service(controller::update_category)
+        .service(controller::patch_category);
 
     scope.service(category_scope)
 }
@@ -24,4 +28,29 @@ pub fn register(scope: Scope) -> Scope {
 #[serde(rename_all = "camelCase")]
 pub struct SubCategoryFilter {
     pub id: Option<i32>,
+    /// Also list categories in the trash
+    pub include_deleted: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryTreeFilter {
+    /// Also list categories in the trash
+    pub include_deleted: Option<bool>,
+}
+
+/// A category with its sub categories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryTreeNode {
+    #[serde(flatten)]
+    pub category: Category,
+    pub children: Vec<CategoryTreeNode>,
+}
+
+pub enum UpdateCategoryResult {
+    Ok(Category),
+    NotFound,
+    /// The parent is the category itself or one of its sub categories
+    InvalidParent,
 }
@@ -2,4 +2,7 @@ pub mod auth;
 pub mod categories;
 pub mod permissions;
 pub mod projects;
+pub mod roles;
+pub mod tasks;
+pub mod tokens;
 pub mod users;
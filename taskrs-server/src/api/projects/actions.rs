@@ -1,21 +1,41 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::{count, now};
 use diesel::prelude::*;
 
 use diesel_pagination::{LoadPaginated, PaginationPage};
-use taskrs_db::models::project::{Project, ProjectColumns};
+use taskrs_db::models::project::{Project, ProjectChangeset, ProjectColumns, ProjectMember};
 use taskrs_db::{Db, DbConnection};
 
+use crate::api::projects::{
+    AddProjectMemberResult, ProjectMemberFilter, ProjectMemberParams, ProjectReference,
+    RemoveProjectMemberResult, UpdateProjectMemberResult,
+};
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult, RestoreEntityParams};
 use crate::models::request_filter::{Order, RequestFilter};
 
+/// Returns a page of projects.
+/// If `visible_for` is set, only projects owned by or shared with that user are returned.
 pub fn get_all_projects(
     filter: RequestFilter<ProjectColumns>,
+    visible_for: Option<i32>,
     conn: &DbConnection,
 ) -> Result<PaginationPage<Project>, diesel::result::Error> {
-    use taskrs_db::schema::projects;
+    use taskrs_db::schema::{project_members, projects};
 
     let mut db_query = projects::table.into_boxed::<Db>();
 
+    // Filter visible projects
+    if let Some(user_id) = visible_for {
+        db_query = db_query.filter(
+            projects::owner_id.eq(user_id).or(projects::id.eq_any(
+                project_members::table
+                    .select(project_members::project_id)
+                    .filter(project_members::user_id.eq(user_id)),
+            )),
+        );
+    }
+
     // Filter query
     if let Some(query) = filter.query {
         let query = format!("%{}%", query);
@@ -26,6 +46,11 @@ pub fn get_all_projects(
         );
     }
 
+    // Deleted projects are only listed on request
+    if !filter.include_deleted.unwrap_or(false) {
+        db_query = db_query.filter(projects::deleted_at.is_null());
+    }
+
     // Order by
     let order_by = filter.order_by.unwrap_or(ProjectColumns::Name);
     let order = filter.order.unwrap_or(Order::Ascending);
@@ -52,6 +77,9 @@ pub fn get_all_projects(
             ProjectColumns::CreatedAt => {
                 db_query.order((projects::created_at.asc(), projects::id.asc()))
             }
+            ProjectColumns::DeletedAt => {
+                db_query.order((projects::deleted_at.asc(), projects::id.asc()))
+            }
         },
         Order::Descending => match order_by {
             ProjectColumns::Id => db_query.order(projects::id.desc()),
@@ -74,6 +102,9 @@ pub fn get_all_projects(
             ProjectColumns::CreatedAt => {
                 db_query.order((projects::created_at.desc(), projects::id.asc()))
             }
+            ProjectColumns::DeletedAt => {
+                db_query.order((projects::deleted_at.desc(), projects::id.asc()))
+            }
         },
     };
 
@@ -89,24 +120,120 @@ pub fn create_project(
         return Ok(CreateEntityResult::Exists);
     }
 
-    Ok(CreateEntityResult::Ok(project.insert(conn)?))
+    conn.transaction::<CreateEntityResult<Project>, diesel::result::Error, _>(|| {
+        let project = project.insert(conn)?;
+
+        // Owner is always an admin member of the project
+        ProjectMember::new(project.id, project.owner_id, true).insert(conn)?;
+
+        Ok(CreateEntityResult::Ok(project))
+    })
 }
 
+/// Moves a project to the trash and removes its members.
+/// Without `cascade` the project must not have tasks or members besides the owner,
+/// with `cascade` the tasks go to the trash with the project until it is purged or restored.
 pub fn delete_project(
     params: DeleteEntityParams,
     conn: &DbConnection,
-) -> diesel::QueryResult<DeleteEntityResult<Project>> {
-    use taskrs_db::schema::projects;
+) -> diesel::QueryResult<DeleteEntityResult<ProjectReference>> {
+    use taskrs_db::schema::{project_members, projects, tasks};
 
-    diesel::delete(projects::table.filter(projects::id.eq(params.id)))
-        .execute(conn)
-        .map(|count| {
-            if count > 0 {
-                Ok(DeleteEntityResult::Ok)
-            } else {
-                Ok(DeleteEntityResult::NotFound)
+    conn.transaction::<DeleteEntityResult<ProjectReference>, diesel::result::Error, _>(|| {
+        let owner_id = match projects::table
+            .find(params.id)
+            .filter(projects::deleted_at.is_null())
+            .select(projects::owner_id)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()?
+        {
+            None => return Ok(DeleteEntityResult::NotFound),
+            Some(owner_id) => owner_id,
+        };
+
+        if params.cascade != Some(true) {
+            let mut references: Vec<ProjectReference> = project_members::table
+                .filter(project_members::project_id.eq(params.id))
+                .filter(project_members::user_id.ne(owner_id))
+                .select(project_members::user_id)
+                .order(project_members::user_id.asc())
+                .load::<i32>(conn)?
+                .into_iter()
+                .map(|user_id| ProjectReference::Member { user_id })
+                .collect();
+            references.extend(
+                tasks::table
+                    .filter(tasks::project_id.eq(params.id))
+                    .filter(tasks::deleted_at.is_null())
+                    .select((tasks::id, tasks::title))
+                    .order(tasks::id.asc())
+                    .load::<(i32, String)>(conn)?
+                    .into_iter()
+                    .map(|(id, title)| ProjectReference::Task { id, title }),
+            );
+
+            if !references.is_empty() {
+                return Ok(DeleteEntityResult::Referenced(references));
             }
-        })?
+        }
+
+        let deleted_at = diesel::update(projects::table.find(params.id))
+            .set(projects::deleted_at.eq(now.nullable()))
+            .returning(projects::deleted_at)
+            .get_result::<Option<NaiveDateTime>>(conn)?;
+
+        // Restoring the project brings back the tasks with the same deletion time
+        diesel::update(
+            tasks::table
+                .filter(tasks::project_id.eq(params.id))
+                .filter(tasks::deleted_at.is_null()),
+        )
+        .set(tasks::deleted_at.eq(deleted_at))
+        .execute(conn)?;
+        diesel::delete(project_members::table.filter(project_members::project_id.eq(params.id)))
+            .execute(conn)?;
+
+        Ok(DeleteEntityResult::Ok)
+    })
+}
+
+/// Restores a project from the trash with the tasks deleted along with it.
+/// The owner becomes a member again, other members have to be added again.
+pub fn restore_project(
+    params: RestoreEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Project>> {
+    use taskrs_db::schema::{projects, tasks};
+
+    conn.transaction::<Option<Project>, diesel::result::Error, _>(|| {
+        let deleted_at = match projects::table
+            .find(params.id)
+            .filter(projects::deleted_at.is_not_null())
+            .select(projects::deleted_at)
+            .for_update()
+            .first::<Option<NaiveDateTime>>(conn)
+            .optional()?
+        {
+            None => return Ok(None),
+            Some(deleted_at) => deleted_at,
+        };
+
+        diesel::update(
+            tasks::table
+                .filter(tasks::project_id.eq(params.id))
+                .filter(tasks::deleted_at.eq(deleted_at)),
+        )
+        .set(tasks::deleted_at.eq(None::<NaiveDateTime>))
+        .execute(conn)?;
+
+        let project = diesel::update(projects::table.find(params.id))
+            .set(projects::deleted_at.eq(None::<NaiveDateTime>))
+            .get_result::<Project>(conn)?;
+        set_owner_membership(project.id, project.owner_id, conn)?;
+
+        Ok(Some(project))
+    })
 }
 
 pub fn update_project(
@@ -115,14 +242,194 @@ pub fn update_project(
 ) -> diesel::QueryResult<Option<Project>> {
     use taskrs_db::schema::projects;
 
-    let target = projects::table.find(project.id);
-    diesel::update(target)
-        .set((
-            projects::name.eq(project.name),
-            projects::description.eq(project.description),
-            projects::category_id.eq(project.category_id),
-            projects::owner_id.eq(project.owner_id),
-        ))
-        .get_result::<Project>(conn)
+    conn.transaction::<Option<Project>, diesel::result::Error, _>(|| {
+        let target = projects::table
+            .find(project.id)
+            .filter(projects::deleted_at.is_null());
+        let project = diesel::update(target)
+            .set((
+                projects::name.eq(project.name),
+                projects::description.eq(project.description),
+                projects::category_id.eq(project.category_id),
+                projects::owner_id.eq(project.owner_id),
+            ))
+            .get_result::<Project>(conn)
+            .optional()?;
+
+        // Owner may have changed
+        if let Some(project) = &project {
+            set_owner_membership(project.id, project.owner_id, conn)?;
+        }
+
+        Ok(project)
+    })
+}
+
+/// Updates the supplied fields of a project
+pub fn patch_project(
+    changes: ProjectChangeset,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<Project>> {
+    use taskrs_db::schema::projects;
+
+    let target = projects::table
+        .find(changes.id)
+        .filter(projects::deleted_at.is_null());
+    if changes.is_empty() {
+        return target.first::<Project>(conn).optional();
+    }
+
+    conn.transaction::<Option<Project>, diesel::result::Error, _>(|| {
+        let project = diesel::update(target)
+            .set(&changes)
+            .get_result::<Project>(conn)
+            .optional()?;
+
+        if let (Some(project), Some(_)) = (&project, changes.owner_id) {
+            set_owner_membership(project.id, project.owner_id, conn)?;
+        }
+
+        Ok(project)
+    })
+}
+
+pub fn get_project_members(
+    filter: ProjectMemberFilter,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Vec<ProjectMember>> {
+    use taskrs_db::schema::{project_members, projects};
+
+    // Members of deleted projects are hidden with the project
+    project_members::table
+        .filter(project_members::project_id.eq(filter.project_id))
+        .filter(
+            project_members::project_id.eq_any(
+                projects::table
+                    .select(projects::id)
+                    .filter(projects::deleted_at.is_null()),
+            ),
+        )
+        .order(project_members::user_id.asc())
+        .load(conn)
+}
+
+pub fn add_project_member(
+    member: ProjectMember,
+    conn: &DbConnection,
+) -> diesel::QueryResult<AddProjectMemberResult> {
+    use taskrs_db::schema::{project_members, users};
+
+    if project_owner(member.project_id, conn)?.is_none() {
+        return Ok(AddProjectMemberResult::InvalidProject);
+    }
+
+    let user_count = users::table
+        .select(count(users::id))
+        .filter(users::id.eq(member.user_id))
+        .filter(users::deleted_at.is_null())
+        .filter(users::activated.eq(true))
+        .first::<i64>(conn)?;
+
+    // User does not exist, is deleted or deactivated
+    if user_count != 1 {
+        return Ok(AddProjectMemberResult::InvalidUser);
+    }
+
+    let existing_member = project_members::table
+        .find((member.project_id, member.user_id))
+        .first::<ProjectMember>(conn)
+        .optional()?;
+
+    if existing_member.is_some() {
+        debug!(
+            "User {} is already member of project {}",
+            member.user_id, member.project_id
+        );
+        return Ok(AddProjectMemberResult::Exists);
+    }
+
+    Ok(AddProjectMemberResult::Ok(member.insert(conn)?))
+}
+
+pub fn remove_project_member(
+    params: ProjectMemberParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<RemoveProjectMemberResult> {
+    use taskrs_db::schema::project_members;
+
+    match project_owner(params.project_id, conn)? {
+        None => return Ok(RemoveProjectMemberResult::InvalidProject),
+        Some(owner_id) if owner_id == params.user_id => {
+            return Ok(RemoveProjectMemberResult::IsOwner)
+        }
+        Some(_) => {}
+    }
+
+    let count = diesel::delete(project_members::table.find((params.project_id, params.user_id)))
+        .execute(conn)?;
+
+    if count > 0 {
+        Ok(RemoveProjectMemberResult::Ok)
+    } else {
+        Ok(RemoveProjectMemberResult::NotFound)
+    }
+}
+
+pub fn update_project_member(
+    member: ProjectMember,
+    conn: &DbConnection,
+) -> diesel::QueryResult<UpdateProjectMemberResult> {
+    use taskrs_db::schema::project_members;
+
+    match project_owner(member.project_id, conn)? {
+        None => return Ok(UpdateProjectMemberResult::InvalidProject),
+        Some(owner_id) if owner_id == member.user_id && !member.is_admin => {
+            return Ok(UpdateProjectMemberResult::IsOwner)
+        }
+        Some(_) => {}
+    }
+
+    let updated_member =
+        diesel::update(project_members::table.find((member.project_id, member.user_id)))
+            .set(project_members::is_admin.eq(member.is_admin))
+            .get_result::<ProjectMember>(conn)
+            .optional()?;
+
+    match updated_member {
+        Some(member) => Ok(UpdateProjectMemberResult::Ok(member)),
+        None => Ok(UpdateProjectMemberResult::NotFound),
+    }
+}
+
+/// Returns the owner of a project or `None` if the project does not exist
+fn project_owner(project_id: i32, conn: &DbConnection) -> diesel::QueryResult<Option<i32>> {
+    use taskrs_db::schema::projects;
+
+    projects::table
+        .find(project_id)
+        .filter(projects::deleted_at.is_null())
+        .select(projects::owner_id)
+        .first::<i32>(conn)
         .optional()
 }
+
+/// Makes sure the owner is an admin member of the project
+fn set_owner_membership(
+    project_id: i32,
+    owner_id: i32,
+    conn: &DbConnection,
+) -> diesel::QueryResult<usize> {
+    use taskrs_db::models::project::NewProjectMember;
+    use taskrs_db::schema::project_members;
+
+    diesel::insert_into(project_members::table)
+        .values(NewProjectMember {
+            project_id,
+            user_id: owner_id,
+            is_admin: true,
+        })
+        .on_conflict((project_members::project_id, project_members::user_id))
+        .do_update()
+        .set(project_members::is_admin.eq(true))
+        .execute(conn)
+}
@@ -1,20 +1,26 @@
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse};
 
-use taskrs_db::models::project::{Project, ProjectColumns};
+use taskrs_db::models::project::{Project, ProjectChangeset, ProjectColumns, ProjectMember};
 use taskrs_db::DbPool;
 
+use crate::api::projects::{
+    AddProjectMemberResult, ProjectMemberFilter, ProjectMemberParams, RemoveProjectMemberResult,
+    UpdateProjectMemberResult,
+};
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult, RestoreEntityParams};
+use crate::models::entity_version::{self, IfMatch, IfMatchResult};
 use crate::models::request_filter::RequestFilter;
 use crate::models::user_token::TokenUser;
 use crate::permissions;
 use crate::utils;
+use crate::utils::ProjectRole;
 
 use super::actions;
 
 /// Returns a list of projects
 ///
-/// Permission: `project_get_all`
+/// Permission: `project_get_all`, otherwise only projects the user owns or is member of
 ///
 #[get("")]
 pub async fn all_projects(
@@ -26,9 +32,13 @@ pub async fn all_projects(
     let filter = filter.into_inner();
 
     // Check permission
-    utils::has_permission(&user, &permissions::PROJECT_GET_ALL, &conn)?;
+    let visible_for = if utils::user_has_permission(&user, &permissions::PROJECT_GET_ALL, &conn)? {
+        None
+    } else {
+        Some(user.id)
+    };
 
-    web::block(move || actions::get_all_projects(filter, &conn))
+    web::block(move || actions::get_all_projects(filter, visible_for, &conn))
         .await
         .map(|categories| HttpResponse::Ok().json(categories))
         .map_err(|e| {
@@ -75,29 +85,78 @@ pub async fn create_project(
 
 /// Delete a project
 ///
-/// Permission: `project_delete`
+/// Permission: `project_delete` or project admin
 ///
 #[delete("")]
 pub async fn delete_project(
     params: web::Query<DeleteEntityParams>,
     user: TokenUser,
     pool: web::Data<DbPool>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let params = params.into_inner();
 
     // Check permission
-    utils::has_permission(&user, &permissions::PROJECT_DELETE, &conn)?;
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_DELETE,
+        params.id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
 
     // Delete project
-    web::block(move || actions::delete_project(params, &conn))
+    web::block(move || {
+        entity_version::if_match::<Project, _, _, _>(params.id, &if_match, &conn, || {
+            actions::delete_project(params, &conn)
+        })
+    })
+    .await
+    .map(|result| match result {
+        IfMatchResult::Ok(DeleteEntityResult::Ok) => HttpResponse::Ok().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::Referenced(references)) => {
+            HttpResponse::BadRequest().json(references)
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Restore a project from the trash
+///
+/// Permission: `project_restore` or project admin
+///
+#[post("/restore")]
+pub async fn restore_project(
+    params: web::Query<RestoreEntityParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_RESTORE,
+        params.id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Restore project
+    web::block(move || actions::restore_project(params, &conn))
         .await
-        .map(|result| match result {
-            DeleteEntityResult::Ok => HttpResponse::Ok().finish(),
-            DeleteEntityResult::NotFound => HttpResponse::NotFound().finish(),
-            DeleteEntityResult::Referenced(references) => {
-                HttpResponse::BadRequest().json(references)
-            }
+        .map(|restored_project| match restored_project {
+            Some(project) => entity_version::versioned_response(project),
+            None => HttpResponse::NotFound().finish(),
         })
         .map_err(|e| {
             error!("{}", e);
@@ -109,26 +168,237 @@ pub async fn delete_project(
 
 /// Update a project
 ///
-/// Permission: `project_update`
+/// Permission: `project_update` or project admin
 ///
 #[put("")]
 pub async fn update_project(
     project: web::Json<Project>,
     user: TokenUser,
     pool: web::Data<DbPool>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let project = project.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_UPDATE,
+        project.id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Update project
+    web::block(move || {
+        entity_version::if_match::<Project, _, _, _>(project.id, &if_match, &conn, || {
+            actions::update_project(project, &conn)
+        })
+    })
+    .await
+    .map(|updated_project| match updated_project {
+        IfMatchResult::Ok(Some(project)) => entity_version::versioned_response(project),
+        IfMatchResult::Ok(None) => HttpResponse::NotFound().finish(),
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Update the supplied fields of a project
+///
+/// Permission: `project_update` or project admin
+///
+#[patch("")]
+pub async fn patch_project(
+    project: web::Json<ProjectChangeset>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let project = project.into_inner();
 
     // Check permission
-    utils::has_permission(&user, &permissions::PROJECT_UPDATE, &conn)?;
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_UPDATE,
+        project.id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
 
     // Update project
-    web::block(move || actions::update_project(project, &conn))
+    web::block(move || {
+        entity_version::if_match::<Project, _, _, _>(project.id, &if_match, &conn, || {
+            actions::patch_project(project, &conn)
+        })
+    })
+    .await
+    .map(|updated_project| match updated_project {
+        IfMatchResult::Ok(Some(project)) => entity_version::versioned_response(project),
+        IfMatchResult::Ok(None) => HttpResponse::NotFound().finish(),
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Returns the members of a project
+///
+/// Permission: `project_member_get_all` or project member
+///
+#[get("/members")]
+pub async fn project_members(
+    user: TokenUser,
+    filter: web::Query<ProjectMemberFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_MEMBER_GET_ALL,
+        filter.project_id,
+        ProjectRole::Member,
+        &conn,
+    )?;
+
+    web::block(move || actions::get_project_members(filter, &conn))
         .await
-        .map(|updated_project| match updated_project {
-            Some(project) => HttpResponse::Ok().json(project),
-            None => HttpResponse::NotFound().finish(),
+        .map(|members| HttpResponse::Ok().json(members))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Adds a user to a project
+///
+/// Permission: `project_member_add` or project admin
+///
+#[post("/members")]
+pub async fn add_project_member(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    new_member: web::Json<ProjectMember>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let new_member = new_member.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_MEMBER_ADD,
+        new_member.project_id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Add member
+    web::block(move || actions::add_project_member(new_member, &conn))
+        .await
+        .map(|result| match result {
+            AddProjectMemberResult::Ok(member) => HttpResponse::Created().json(member),
+            AddProjectMemberResult::Exists => HttpResponse::BadRequest().finish(),
+            AddProjectMemberResult::InvalidProject => HttpResponse::NotFound().finish(),
+            AddProjectMemberResult::InvalidUser => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Removes a user from a project. The owner can not be removed.
+///
+/// Permission: `project_member_remove` or project admin
+///
+#[delete("/members")]
+pub async fn remove_project_member(
+    params: web::Query<ProjectMemberParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_MEMBER_REMOVE,
+        params.project_id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Remove member
+    web::block(move || actions::remove_project_member(params, &conn))
+        .await
+        .map(|result| match result {
+            RemoveProjectMemberResult::Ok => HttpResponse::Ok().finish(),
+            RemoveProjectMemberResult::IsOwner => {
+                HttpResponse::BadRequest().body("Project owner can not be removed")
+            }
+            RemoveProjectMemberResult::NotFound | RemoveProjectMemberResult::InvalidProject => {
+                HttpResponse::NotFound().finish()
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Promotes or demotes a member of a project. The owner can not be demoted.
+///
+/// Permission: `project_member_update` or project admin
+///
+#[put("/members")]
+pub async fn update_project_member(
+    member: web::Json<ProjectMember>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let member = member.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::PROJECT_MEMBER_UPDATE,
+        member.project_id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Update member
+    web::block(move || actions::update_project_member(member, &conn))
+        .await
+        .map(|result| match result {
+            UpdateProjectMemberResult::Ok(member) => HttpResponse::Ok().json(member),
+            UpdateProjectMemberResult::IsOwner => {
+                HttpResponse::BadRequest().body("Project owner can not be demoted")
+            }
+            UpdateProjectMemberResult::NotFound | UpdateProjectMemberResult::InvalidProject => {
+                HttpResponse::NotFound().finish()
+            }
         })
         .map_err(|e| {
             error!("{}", e);
@@ -1,4 +1,7 @@
 use actix_web::{web, Scope};
+use serde::{Deserialize, Serialize};
+
+use taskrs_db::models::project::ProjectMember;
 
 mod actions;
 mod controller;
@@ -6,14 +9,65 @@ mod controller;
 pub fn register(scope: Scope) -> Scope {
     let mut project_scope = web::scope("projects").wrap(crate::middleware::auth::Authentication);
 
-    // Debug routes
-    if cfg!(debug_assertions) {}
-
     project_scope = project_scope
         .service(controller::all_projects)
         .service(controller::create_project)
         .service(controller::delete_project)
-        .service(controller::update_project);
+        .service(controller::restore_project)
+        .service(controller::update_project)
+        .service(controller::patch_project)
+        .service(controller::project_members)
+        .service(controller::add_project_member)
+        .service(controller::remove_project_member)
+        .service(controller::update_project_member);
 
     scope.service(project_scope)
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMemberFilter {
+    pub project_id: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMemberParams {
+    pub project_id: i32,
+    pub user_id: i32,
+}
+
+/// Keeps a project from being deleted without `cascade`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ProjectReference {
+    #[serde(rename_all = "camelCase")]
+    Member {
+        user_id: i32,
+    },
+    Task {
+        id: i32,
+        title: String,
+    },
+}
+
+pub enum AddProjectMemberResult {
+    Ok(ProjectMember),
+    Exists,
+    InvalidProject,
+    InvalidUser,
+}
+
+pub enum RemoveProjectMemberResult {
+    Ok,
+    NotFound,
+    IsOwner,
+    InvalidProject,
+}
+
+pub enum UpdateProjectMemberResult {
+    Ok(ProjectMember),
+    NotFound,
+    IsOwner,
+    InvalidProject,
+}
@@ -0,0 +1,239 @@
+use diesel::dsl::count;
+use diesel::prelude::*;
+
+use diesel_pagination::{LoadPaginated, PaginationPage};
+use taskrs_db::models::permission::Permission;
+use taskrs_db::models::role::{NewRolePermission, NewUserRole, Role, RoleColumns, UserRole};
+use taskrs_db::{Db, DbConnection};
+
+use crate::api::roles::{ChangeRoleResult, RoleFilter, RolePermissionsDto, UserRolesDto};
+use crate::models::create_entity_result::CreateEntityResult;
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::request_filter::{Order, RequestFilter};
+use crate::utils::{update_permission_cache_for_role, update_permission_cache_for_user};
+
+pub fn get_all_roles(
+    filter: RequestFilter<RoleColumns>,
+    conn: &DbConnection,
+) -> Result<PaginationPage<Role>, diesel::result::Error> {
+    use taskrs_db::schema::roles;
+
+    let mut db_query = roles::table.into_boxed::<Db>();
+
+    // Filter query
+    if let Some(query) = filter.query {
+        let query = format!("%{}%", query);
+        db_query = db_query.filter(
+            roles::name
+                .like(query.clone())
+                .or(roles::description.like(query)),
+        );
+    }
+
+    // Order by
+    let order_by = filter.order_by.unwrap_or(RoleColumns::Name);
+    let order = filter.order.unwrap_or(Order::Ascending);
+
+    db_query = match order {
+        Order::Ascending => match order_by {
+            RoleColumns::Id => db_query.order(roles::id.asc()),
+            RoleColumns::Name => db_query.order((roles::name.asc(), roles::id.asc())),
+            RoleColumns::Description => db_query.order((roles::description.asc(), roles::id.asc())),
+            RoleColumns::UpdatedAt => db_query.order((roles::updated_at.asc(), roles::id.asc())),
+            RoleColumns::CreatedAt => db_query.order((roles::created_at.asc(), roles::id.asc())),
+        },
+        Order::Descending => match order_by {
+            RoleColumns::Id => db_query.order(roles::id.desc()),
+            RoleColumns::Name => db_query.order((roles::name.desc(), roles::id.asc())),
+            RoleColumns::Description => {
+                db_query.order((roles::description.desc(), roles::id.asc()))
+            }
+            RoleColumns::UpdatedAt => db_query.order((roles::updated_at.desc(), roles::id.asc())),
+            RoleColumns::CreatedAt => db_query.order((roles::created_at.desc(), roles::id.asc())),
+        },
+    };
+
+    db_query.load_with_pagination(conn, filter.page, filter.limit)
+}
+
+pub fn create_role(
+    role: Role,
+    conn: &DbConnection,
+) -> diesel::QueryResult<CreateEntityResult<Role>> {
+    if role.exists(conn)? {
+        debug!("Role '{}' already exists", &role.name);
+        return Ok(CreateEntityResult::Exists);
+    }
+
+    Ok(CreateEntityResult::Ok(role.insert(conn)?))
+}
+
+pub fn delete_role(
+    params: DeleteEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<DeleteEntityResult<UserRole>> {
+    use taskrs_db::schema::{roles, user_roles};
+
+    conn.transaction::<DeleteEntityResult<UserRole>, diesel::result::Error, _>(|| {
+        let user_roles: Vec<UserRole> = user_roles::table
+            .filter(user_roles::role_id.eq(params.id))
+            .load(conn)?;
+
+        if !user_roles.is_empty() && params.cascade != Some(true) {
+            return Ok(DeleteEntityResult::Referenced(user_roles));
+        }
+
+        // Role permissions and user roles are deleted by the database
+        let count = diesel::delete(roles::table.filter(roles::id.eq(params.id))).execute(conn)?;
+
+        for user_role in user_roles {
+            update_permission_cache_for_user(user_role.user_id, conn)?;
+        }
+
+        if count > 0 {
+            Ok(DeleteEntityResult::Ok)
+        } else {
+            Ok(DeleteEntityResult::NotFound)
+        }
+    })
+}
+
+pub fn update_role(role: Role, conn: &DbConnection) -> diesel::QueryResult<Option<Role>> {
+    use taskrs_db::schema::roles;
+
+    let target = roles::table.find(role.id);
+    diesel::update(target)
+        .set((
+            roles::name.eq(role.name),
+            roles::description.eq(role.description),
+        ))
+        .get_result::<Role>(conn)
+        .optional()
+}
+
+pub fn role_permissions(
+    filter: RoleFilter,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Vec<Permission>> {
+    use taskrs_db::schema::{permissions, role_permissions};
+
+    permissions::table
+        .inner_join(role_permissions::table)
+        .filter(role_permissions::role_id.eq(filter.id))
+        .select(permissions::all_columns)
+        .order(permissions::name.asc())
+        .load(conn)
+}
+
+pub fn set_role_permissions(
+    new_permissions: RolePermissionsDto,
+    conn: &DbConnection,
+) -> Result<ChangeRoleResult, diesel::result::Error> {
+    use taskrs_db::schema::{role_permissions, roles};
+
+    let count = roles::table
+        .select(count(roles::id))
+        .filter(roles::id.eq(&new_permissions.role_id))
+        .first::<i64>(conn)?;
+
+    // Role does not exist
+    if count != 1 {
+        return Ok(ChangeRoleResult::InvalidRole);
+    }
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::delete(
+            role_permissions::table.filter(role_permissions::role_id.eq(&new_permissions.role_id)),
+        )
+        .execute(conn)?;
+
+        let role_id = new_permissions.role_id;
+        let new_permissions = new_permissions
+            .permission_ids
+            .iter()
+            .map(|permission_id| NewRolePermission {
+                role_id,
+                permission_id: *permission_id,
+            })
+            .collect::<Vec<NewRolePermission>>();
+
+        diesel::insert_into(role_permissions::table)
+            .values(&new_permissions)
+            .execute(conn)?;
+
+        update_permission_cache_for_role(role_id, conn)
+    })?;
+
+    Ok(ChangeRoleResult::Ok)
+}
+
+pub fn grant_roles(
+    new_roles: UserRolesDto,
+    conn: &DbConnection,
+) -> Result<ChangeRoleResult, diesel::result::Error> {
+    use taskrs_db::schema::{user_roles, users};
+
+    let count = users::table
+        .select(count(users::id))
+        .filter(users::id.eq(&new_roles.user_id))
+        .first::<i64>(conn)?;
+
+    // User does not exist
+    if count != 1 {
+        return Ok(ChangeRoleResult::InvalidUser);
+    }
+
+    let current_roles: Vec<i32> = user_roles::table
+        .select(user_roles::role_id)
+        .filter(user_roles::user_id.eq(&new_roles.user_id))
+        .load::<i32>(conn)?;
+
+    let user_id = new_roles.user_id;
+    let new_roles = new_roles
+        .role_ids
+        .iter()
+        .filter(|role_id| !current_roles.contains(role_id))
+        .map(|role_id| NewUserRole {
+            user_id,
+            role_id: *role_id,
+        })
+        .collect::<Vec<NewUserRole>>();
+
+    diesel::insert_into(user_roles::table)
+        .values(&new_roles)
+        .execute(conn)?;
+
+    update_permission_cache_for_user(user_id, conn)?;
+
+    Ok(ChangeRoleResult::Ok)
+}
+
+pub fn revoke_roles(
+    old_roles: UserRolesDto,
+    conn: &DbConnection,
+) -> Result<ChangeRoleResult, diesel::result::Error> {
+    use taskrs_db::schema::{user_roles, users};
+
+    let count = users::table
+        .select(count(users::id))
+        .filter(users::id.eq(&old_roles.user_id))
+        .first::<i64>(conn)?;
+
+    // User does not exist
+    if count != 1 {
+        return Ok(ChangeRoleResult::InvalidUser);
+    }
+
+    diesel::delete(
+        user_roles::table.filter(
+            user_roles::user_id
+                .eq(&old_roles.user_id)
+                .and(user_roles::role_id.eq_any(old_roles.role_ids)),
+        ),
+    )
+    .execute(conn)?;
+
+    update_permission_cache_for_user(old_roles.user_id, conn)?;
+
+    Ok(ChangeRoleResult::Ok)
+}
@@ -0,0 +1,252 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use taskrs_db::models::role::{Role, RoleColumns};
+use taskrs_db::DbPool;
+
+use crate::api::roles::{ChangeRoleResult, RoleFilter, RolePermissionsDto, UserRolesDto};
+use crate::models::create_entity_result::CreateEntityResult;
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::request_filter::RequestFilter;
+use crate::models::user_token::TokenUser;
+use crate::permissions;
+use crate::utils;
+
+use super::actions;
+
+/// Returns a list of roles
+///
+/// Permission: `role_get_all`
+#[get("")]
+pub async fn all_roles(
+    user: TokenUser,
+    filter: web::Query<RequestFilter<RoleColumns>>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_GET_ALL, &conn)?;
+
+    web::block(move || actions::get_all_roles(filter, &conn))
+        .await
+        .map(|roles| HttpResponse::Ok().json(roles))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Creates a new role
+///
+/// Permission: `role_create`
+#[post("")]
+pub async fn create_role(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    new_role: web::Json<Role>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let new_role = new_role.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_CREATE, &conn)?;
+
+    // Create role
+    web::block(move || actions::create_role(new_role, &conn))
+        .await
+        .map(|created_role| match created_role {
+            CreateEntityResult::Ok(role) => HttpResponse::Created().json(role),
+            CreateEntityResult::Exists => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Delete a role. Returns the users that have the role unless `cascade` is set.
+///
+/// Permission: `role_delete`
+#[delete("")]
+pub async fn delete_role(
+    params: web::Query<DeleteEntityParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_DELETE, &conn)?;
+
+    // Delete role
+    web::block(move || actions::delete_role(params, &conn))
+        .await
+        .map(|result| match result {
+            DeleteEntityResult::Ok => HttpResponse::Ok().finish(),
+            DeleteEntityResult::NotFound => HttpResponse::NotFound().finish(),
+            DeleteEntityResult::Referenced(references) => {
+                HttpResponse::BadRequest().json(references)
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Update a role
+///
+/// Permission: `role_update`
+#[put("")]
+pub async fn update_role(
+    role: web::Json<Role>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let role = role.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_UPDATE, &conn)?;
+
+    // Update role
+    web::block(move || actions::update_role(role, &conn))
+        .await
+        .map(|updated_role| match updated_role {
+            Some(role) => HttpResponse::Ok().json(role),
+            None => HttpResponse::NotFound().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Returns the permissions of a role
+///
+/// Permission: `role_get_all`
+#[get("/permissions")]
+pub async fn role_permissions(
+    user: TokenUser,
+    filter: web::Query<RoleFilter>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let filter = filter.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_GET_ALL, &conn)?;
+
+    web::block(move || actions::role_permissions(filter, &conn))
+        .await
+        .map(|permissions| HttpResponse::Ok().json(permissions))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Sets the permissions of a role
+///
+/// Permission: `role_update`
+#[post("/permissions")]
+pub async fn set_role_permissions(
+    user: TokenUser,
+    new_permissions: web::Json<RolePermissionsDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let new_permissions = new_permissions.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_UPDATE, &conn)?;
+
+    web::block(move || actions::set_role_permissions(new_permissions, &conn))
+        .await
+        .map(|res| match res {
+            ChangeRoleResult::Ok => HttpResponse::Ok().finish(),
+            ChangeRoleResult::InvalidRole | ChangeRoleResult::InvalidUser => {
+                HttpResponse::BadRequest().finish()
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Grants roles to a user
+///
+/// Permission: `role_grant`
+#[post("/grant")]
+pub async fn grant_roles(
+    user: TokenUser,
+    new_roles: web::Json<UserRolesDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let new_roles = new_roles.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_GRANT, &conn)?;
+
+    web::block(move || actions::grant_roles(new_roles, &conn))
+        .await
+        .map(|res| match res {
+            ChangeRoleResult::Ok => HttpResponse::Ok().finish(),
+            ChangeRoleResult::InvalidRole | ChangeRoleResult::InvalidUser => {
+                HttpResponse::BadRequest().finish()
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Revokes roles from a user
+///
+/// Permission: `role_revoke`
+#[post("/revoke")]
+pub async fn revoke_roles(
+    user: TokenUser,
+    old_roles: web::Json<UserRolesDto>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let old_roles = old_roles.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::ROLE_REVOKE, &conn)?;
+
+    web::block(move || actions::revoke_roles(old_roles, &conn))
+        .await
+        .map(|res| match res {
+            ChangeRoleResult::Ok => HttpResponse::Ok().finish(),
+            ChangeRoleResult::InvalidRole | ChangeRoleResult::InvalidUser => {
+                HttpResponse::BadRequest().finish()
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
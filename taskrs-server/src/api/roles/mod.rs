@@ -0,0 +1,47 @@
+use actix_web::{web, Scope};
+use serde::{Deserialize, Serialize};
+
+mod actions;
+mod controller;
+
+pub fn register(scope: Scope) -> Scope {
+    let mut role_scope = web::scope("roles").wrap(crate::middleware::auth::Authentication);
+
+    role_scope = role_scope
+        .service(controller::all_roles)
+        .service(controller::create_role)
+        .service(controller::delete_role)
+        .service(controller::update_role)
+        .service(controller::role_permissions)
+        .service(controller::set_role_permissions)
+        .service(controller::grant_roles)
+        .service(controller::revoke_roles);
+
+    scope.service(role_scope)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleFilter {
+    pub id: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePermissionsDto {
+    pub role_id: i32,
+    pub permission_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRolesDto {
+    pub user_id: i32,
+    pub role_ids: Vec<i32>,
+}
+
+pub enum ChangeRoleResult {
+    Ok,
+    InvalidRole,
+    InvalidUser,
+}
@@ -0,0 +1,170 @@
+use diesel::dsl::count;
+use diesel::prelude::*;
+
+use diesel_pagination::{LoadPaginated, PaginationPage};
+use taskrs_db::models::task::{Task, TaskColumns};
+use taskrs_db::{Db, DbConnection};
+
+use crate::api::tasks::TaskProjectFilter;
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::request_filter::{Order, RequestFilter};
+
+/// Returns a page of tasks. Tasks of deleted projects are hidden.
+/// If `visible_for` is set, only tasks of projects owned by or shared with that user are returned.
+pub fn get_all_tasks(
+    project_filter: TaskProjectFilter,
+    filter: RequestFilter<TaskColumns>,
+    visible_for: Option<i32>,
+    conn: &DbConnection,
+) -> Result<PaginationPage<Task>, diesel::result::Error> {
+    use taskrs_db::schema::{project_members, projects, tasks};
+
+    let mut db_query = tasks::table
+        .filter(tasks::deleted_at.is_null())
+        .filter(
+            tasks::project_id.eq_any(
+                projects::table
+                    .select(projects::id)
+                    .filter(projects::deleted_at.is_null()),
+            ),
+        )
+        .into_boxed::<Db>();
+
+    // Filter visible projects
+    if let Some(user_id) = visible_for {
+        db_query = db_query.filter(
+            tasks::project_id.eq_any(
+                projects::table.select(projects::id).filter(
+                    projects::owner_id.eq(user_id).or(projects::id.eq_any(
+                        project_members::table
+                            .select(project_members::project_id)
+                            .filter(project_members::user_id.eq(user_id)),
+                    )),
+                ),
+            ),
+        );
+    }
+
+    // Filter project
+    if let Some(project_id) = project_filter.project_id {
+        db_query = db_query.filter(tasks::project_id.eq(project_id));
+    }
+
+    // Filter query
+    if let Some(query) = filter.query {
+        let query = format!("%{}%", query);
+        db_query = db_query.filter(
+            tasks::title
+                .like(query.clone())
+                .or(tasks::description.like(query)),
+        );
+    }
+
+    // Order by
+    let order_by = filter.order_by.unwrap_or(TaskColumns::CreatedAt);
+    let order = filter.order.unwrap_or(Order::Ascending);
+
+    db_query = match order {
+        Order::Ascending => match order_by {
+            TaskColumns::Id => db_query.order(tasks::id.asc()),
+            TaskColumns::ProjectId => db_query.order((tasks::project_id.asc(), tasks::id.asc())),
+            TaskColumns::Title => db_query.order((tasks::title.asc(), tasks::id.asc())),
+            TaskColumns::Description => db_query.order((tasks::description.asc(), tasks::id.asc())),
+            TaskColumns::Status => db_query.order((tasks::status.asc(), tasks::id.asc())),
+            TaskColumns::Priority => db_query.order((tasks::priority.asc(), tasks::id.asc())),
+            TaskColumns::AssigneeId => db_query.order((tasks::assignee_id.asc(), tasks::id.asc())),
+            TaskColumns::ReporterId => db_query.order((tasks::reporter_id.asc(), tasks::id.asc())),
+            TaskColumns::DueDate => db_query.order((tasks::due_date.asc(), tasks::id.asc())),
+            TaskColumns::UpdatedAt => db_query.order((tasks::updated_at.asc(), tasks::id.asc())),
+            TaskColumns::CreatedAt => db_query.order((tasks::created_at.asc(), tasks::id.asc())),
+        },
+        Order::Descending => match order_by {
+            TaskColumns::Id => db_query.order(tasks::id.desc()),
+            TaskColumns::ProjectId => db_query.order((tasks::project_id.desc(), tasks::id.asc())),
+            TaskColumns::Title => db_query.order((tasks::title.desc(), tasks::id.asc())),
+            TaskColumns::Description => {
+                db_query.order((tasks::description.desc(), tasks::id.asc()))
+            }
+            TaskColumns::Status => db_query.order((tasks::status.desc(), tasks::id.asc())),
+            TaskColumns::Priority => db_query.order((tasks::priority.desc(), tasks::id.asc())),
+            TaskColumns::AssigneeId => db_query.order((tasks::assignee_id.desc(), tasks::id.asc())),
+            TaskColumns::ReporterId => db_query.order((tasks::reporter_id.desc(), tasks::id.asc())),
+            TaskColumns::DueDate => db_query.order((tasks::due_date.desc(), tasks::id.asc())),
+            TaskColumns::UpdatedAt => db_query.order((tasks::updated_at.desc(), tasks::id.asc())),
+            TaskColumns::CreatedAt => db_query.order((tasks::created_at.desc(), tasks::id.asc())),
+        },
+    };
+
+    db_query.load_with_pagination(conn, filter.page, filter.limit)
+}
+
+/// Creates a task inside its project.
+/// Returns `None` if the project does not exist or is deleted.
+pub fn create_task(task: Task, conn: &DbConnection) -> diesel::QueryResult<Option<Task>> {
+    use taskrs_db::schema::projects;
+
+    let count = projects::table
+        .select(count(projects::id))
+        .filter(projects::id.eq(task.project_id))
+        .filter(projects::deleted_at.is_null())
+        .first::<i64>(conn)?;
+
+    // Project does not exist
+    if count != 1 {
+        debug!("Project {} for task does not exist", task.project_id);
+        return Ok(None);
+    }
+
+    Ok(Some(task.insert(conn)?))
+}
+
+pub fn delete_task(
+    params: DeleteEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<DeleteEntityResult<Task>> {
+    use taskrs_db::schema::tasks;
+
+    let target = tasks::table
+        .filter(tasks::id.eq(params.id))
+        .filter(tasks::deleted_at.is_null());
+    let count = diesel::delete(target).execute(conn)?;
+
+    if count > 0 {
+        Ok(DeleteEntityResult::Ok)
+    } else {
+        Ok(DeleteEntityResult::NotFound)
+    }
+}
+
+pub fn update_task(task: Task, conn: &DbConnection) -> diesel::QueryResult<Option<Task>> {
+    use taskrs_db::schema::tasks;
+
+    let target = tasks::table
+        .find(task.id)
+        .filter(tasks::deleted_at.is_null());
+    diesel::update(target)
+        .set((
+            tasks::title.eq(task.title),
+            tasks::description.eq(task.description),
+            tasks::status.eq(task.status),
+            tasks::priority.eq(task.priority),
+            tasks::assignee_id.eq(task.assignee_id),
+            tasks::due_date.eq(task.due_date),
+        ))
+        .get_result::<Task>(conn)
+        .optional()
+}
+
+/// Returns the project of a task or `None` if the task does not exist or its project is deleted
+pub fn task_project_id(task_id: i32, conn: &DbConnection) -> diesel::QueryResult<Option<i32>> {
+    use taskrs_db::schema::{projects, tasks};
+
+    tasks::table
+        .inner_join(projects::table)
+        .filter(tasks::id.eq(task_id))
+        .filter(tasks::deleted_at.is_null())
+        .filter(projects::deleted_at.is_null())
+        .select(tasks::project_id)
+        .first::<i32>(conn)
+        .optional()
+}
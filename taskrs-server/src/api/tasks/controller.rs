@@ -0,0 +1,205 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use taskrs_db::models::task::{Task, TaskColumns};
+use taskrs_db::{DbConnection, DbPool};
+
+use crate::api::tasks::TaskProjectFilter;
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::entity_version::{self, IfMatch, IfMatchResult};
+use crate::models::request_filter::RequestFilter;
+use crate::models::user_token::TokenUser;
+use crate::permissions;
+use crate::utils;
+use crate::utils::ProjectRole;
+
+use super::actions;
+
+/// Returns a list of tasks, optionally limited to one project
+///
+/// Permission: `task_get_all` or project member, otherwise only tasks of projects the user owns
+/// or is member of
+///
+#[get("")]
+pub async fn all_tasks(
+    user: TokenUser,
+    project_filter: web::Query<TaskProjectFilter>,
+    filter: web::Query<RequestFilter<TaskColumns>>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let project_filter = project_filter.into_inner();
+    let filter = filter.into_inner();
+
+    // Check permission
+    let visible_for = match project_filter.project_id {
+        Some(project_id) => {
+            utils::has_project_permission(
+                &user,
+                &permissions::TASK_GET_ALL,
+                project_id,
+                ProjectRole::Member,
+                &conn,
+            )?;
+            None
+        }
+        None => {
+            if utils::user_has_permission(&user, &permissions::TASK_GET_ALL, &conn)? {
+                None
+            } else {
+                Some(user.id)
+            }
+        }
+    };
+
+    web::block(move || actions::get_all_tasks(project_filter, filter, visible_for, &conn))
+        .await
+        .map(|tasks| HttpResponse::Ok().json(tasks))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Creates a new task
+///
+/// Permission: `task_create` or project member
+///
+#[post("")]
+pub async fn create_task(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    new_task: web::Json<Task>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let mut new_task = new_task.into_inner();
+
+    // Check permission
+    utils::has_project_permission(
+        &user,
+        &permissions::TASK_CREATE,
+        new_task.project_id,
+        ProjectRole::Member,
+        &conn,
+    )?;
+
+    // Set task reporter
+    new_task.reporter_id = Some(user.id);
+
+    // Create task
+    web::block(move || actions::create_task(new_task, &conn))
+        .await
+        .map(|created_task| match created_task {
+            Some(task) => HttpResponse::Created().json(task),
+            None => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Delete a task
+///
+/// Permission: `task_delete` or project admin
+///
+#[delete("")]
+pub async fn delete_task(
+    params: web::Query<DeleteEntityParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    let project_id = task_project_id(params.id, &conn)?;
+    utils::has_project_permission(
+        &user,
+        &permissions::TASK_DELETE,
+        project_id,
+        ProjectRole::Admin,
+        &conn,
+    )?;
+
+    // Delete task
+    web::block(move || {
+        entity_version::if_match::<Task, _, _, _>(params.id, &if_match, &conn, || {
+            actions::delete_task(params, &conn)
+        })
+    })
+    .await
+    .map(|result| match result {
+        IfMatchResult::Ok(DeleteEntityResult::Ok) => HttpResponse::Ok().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(DeleteEntityResult::Referenced(references)) => {
+            HttpResponse::BadRequest().json(references)
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Update a task
+///
+/// Permission: `task_update` or project member
+///
+#[put("")]
+pub async fn update_task(
+    task: web::Json<Task>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let task = task.into_inner();
+
+    // Check permission
+    let project_id = task_project_id(task.id, &conn)?;
+    utils::has_project_permission(
+        &user,
+        &permissions::TASK_UPDATE,
+        project_id,
+        ProjectRole::Member,
+        &conn,
+    )?;
+
+    // Update task
+    web::block(move || {
+        entity_version::if_match::<Task, _, _, _>(task.id, &if_match, &conn, || {
+            actions::update_task(task, &conn)
+        })
+    })
+    .await
+    .map(|updated_task| match updated_task {
+        IfMatchResult::Ok(Some(task)) => entity_version::versioned_response(task),
+        IfMatchResult::Ok(None) => HttpResponse::NotFound().finish(),
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Looks up the project of an existing task.
+/// Returns NotFound if the task does not exist.
+fn task_project_id(task_id: i32, conn: &DbConnection) -> Result<i32, actix_web::Error> {
+    actions::task_project_id(task_id, conn)
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        })?
+        .ok_or_else(|| HttpResponse::NotFound().finish().into())
+}
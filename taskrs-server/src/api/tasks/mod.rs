@@ -0,0 +1,23 @@
+use actix_web::{web, Scope};
+use serde::{Deserialize, Serialize};
+
+mod actions;
+mod controller;
+
+pub fn register(scope: Scope) -> Scope {
+    let mut task_scope = web::scope("tasks").wrap(crate::middleware::auth::Authentication);
+
+    task_scope = task_scope
+        .service(controller::all_tasks)
+        .service(controller::create_task)
+        .service(controller::delete_task)
+        .service(controller::update_task);
+
+    scope.service(task_scope)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskProjectFilter {
+    pub project_id: Option<i32>,
+}
@@ -0,0 +1,141 @@
+use diesel::prelude::*;
+
+use taskrs_db::models::api_token::{ApiToken, NewApiTokenPermission};
+use taskrs_db::models::permission::Permission;
+use taskrs_db::token::{generate_token, hash_token};
+use taskrs_db::DbConnection;
+
+use crate::api::tokens::{
+    ApiTokenDto, CreateApiTokenDto, CreateApiTokenResult, CreatedApiTokenDto,
+};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::user_token::API_TOKEN_PREFIX;
+use crate::utils::update_permission_cache_for_user;
+
+pub fn get_api_tokens(user_id: i32, conn: &DbConnection) -> diesel::QueryResult<Vec<ApiTokenDto>> {
+    use taskrs_db::schema::api_tokens;
+
+    let api_tokens = api_tokens::table
+        .filter(api_tokens::user_id.eq(user_id))
+        .order(api_tokens::name.asc())
+        .load::<ApiToken>(conn)?;
+
+    api_tokens
+        .into_iter()
+        .map(|api_token| {
+            Ok(ApiTokenDto {
+                permissions: api_token_permissions(api_token.id, conn)?,
+                api_token,
+            })
+        })
+        .collect()
+}
+
+/// Creates a new API token for the user.
+/// The token can only get permissions the user has.
+pub fn create_api_token(
+    user_id: i32,
+    new_token: CreateApiTokenDto,
+    conn: &DbConnection,
+) -> diesel::QueryResult<CreateApiTokenResult> {
+    use taskrs_db::schema::{api_token_permissions, permissions};
+
+    let user_permissions = update_permission_cache_for_user(user_id, conn)?;
+    let db_permissions: Vec<Permission> = permissions::table
+        .filter(permissions::id.eq_any(&new_token.permission_ids))
+        .load(conn)?;
+
+    let invalid_permissions: Vec<i32> = new_token
+        .permission_ids
+        .iter()
+        .filter(|permission_id| {
+            !db_permissions.iter().any(|permission| {
+                permission.id == **permission_id && user_permissions.contains(&permission.name)
+            })
+        })
+        .copied()
+        .collect();
+
+    if !invalid_permissions.is_empty() {
+        debug!(
+            "User {} can't grant permissions {:?}",
+            user_id, invalid_permissions
+        );
+        return Ok(CreateApiTokenResult::InvalidPermissions(
+            invalid_permissions,
+        ));
+    }
+
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        let api_token = ApiToken {
+            user_id,
+            name: new_token.name,
+            token_hash: hash_token(&token),
+            exp: new_token.exp,
+            ..Default::default()
+        }
+        .insert(conn)?;
+
+        let new_permissions = db_permissions
+            .iter()
+            .map(|permission| NewApiTokenPermission {
+                api_token_id: api_token.id,
+                permission_id: permission.id,
+            })
+            .collect::<Vec<NewApiTokenPermission>>();
+
+        diesel::insert_into(api_token_permissions::table)
+            .values(&new_permissions)
+            .execute(conn)?;
+
+        Ok(CreateApiTokenResult::Ok(CreatedApiTokenDto {
+            token,
+            api_token: ApiTokenDto {
+                api_token,
+                permissions: db_permissions
+                    .into_iter()
+                    .map(|permission| permission.name)
+                    .collect(),
+            },
+        }))
+    })
+}
+
+/// Revokes an API token of the user
+pub fn delete_api_token(
+    user_id: i32,
+    params: DeleteEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<DeleteEntityResult<ApiToken>> {
+    use taskrs_db::schema::api_tokens;
+
+    // Permissions are deleted by the database
+    let count = diesel::delete(
+        api_tokens::table
+            .filter(api_tokens::id.eq(params.id))
+            .filter(api_tokens::user_id.eq(user_id)),
+    )
+    .execute(conn)?;
+
+    if count > 0 {
+        Ok(DeleteEntityResult::Ok)
+    } else {
+        Ok(DeleteEntityResult::NotFound)
+    }
+}
+
+fn api_token_permissions(
+    api_token_id: i32,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Vec<String>> {
+    use taskrs_db::schema::{api_token_permissions, permissions};
+
+    permissions::table
+        .inner_join(api_token_permissions::table)
+        .filter(api_token_permissions::api_token_id.eq(api_token_id))
+        .select(permissions::name)
+        .order(permissions::name.asc())
+        .load(conn)
+}
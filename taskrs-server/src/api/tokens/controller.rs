@@ -0,0 +1,89 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+
+use taskrs_db::DbPool;
+
+use crate::api::tokens::{CreateApiTokenDto, CreateApiTokenResult};
+use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::user_token::{JwtUser, TokenUser};
+use crate::utils;
+
+use super::actions;
+
+/// Returns the API tokens of the current user
+///
+/// Permission: none
+#[get("")]
+pub async fn all_tokens(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+
+    web::block(move || actions::get_api_tokens(user.id, &conn))
+        .await
+        .map(|tokens| HttpResponse::Ok().json(tokens))
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Creates a new API token for the current user. The token is only returned once.
+/// API tokens can't be used to create further tokens.
+///
+/// Permission: none, the token can only get permissions the user has
+#[post("")]
+pub async fn create_token(
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+    new_token: web::Json<CreateApiTokenDto>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let new_token = new_token.into_inner();
+
+    web::block(move || actions::create_api_token(user.id, new_token, &conn))
+        .await
+        .map(|created_token| match created_token {
+            CreateApiTokenResult::Ok(token) => HttpResponse::Created().json(token),
+            CreateApiTokenResult::InvalidPermissions(permission_ids) => {
+                HttpResponse::BadRequest().json(permission_ids)
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Revokes an API token of the current user
+///
+/// Permission: none
+#[delete("")]
+pub async fn delete_token(
+    params: web::Query<DeleteEntityParams>,
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    web::block(move || actions::delete_api_token(user.id, params, &conn))
+        .await
+        .map(|result| match result {
+            DeleteEntityResult::Ok => HttpResponse::Ok().finish(),
+            DeleteEntityResult::NotFound => HttpResponse::NotFound().finish(),
+            DeleteEntityResult::Referenced(references) => {
+                HttpResponse::BadRequest().json(references)
+            }
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
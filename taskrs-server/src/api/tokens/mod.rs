@@ -0,0 +1,50 @@
+use actix_web::{web, Scope};
+use serde::{Deserialize, Serialize};
+
+use taskrs_db::models::api_token::ApiToken;
+
+mod actions;
+mod controller;
+
+pub fn register(scope: Scope) -> Scope {
+    let mut token_scope = web::scope("tokens").wrap(crate::middleware::auth::Authentication);
+
+    token_scope = token_scope
+        .service(controller::all_tokens)
+        .service(controller::create_token)
+        .service(controller::delete_token);
+
+    scope.service(token_scope)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenDto {
+    pub name: String,
+    /// Unix timestamp, the token never expires if not set
+    pub exp: Option<i64>,
+    pub permission_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTokenDto {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiTokenDto {
+    /// Only returned once
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiTokenDto,
+}
+
+pub enum CreateApiTokenResult {
+    Ok(CreatedApiTokenDto),
+    /// Permissions that don't exist or the user doesn't have
+    InvalidPermissions(Vec<i32>),
+}
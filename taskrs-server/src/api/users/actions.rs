@@ -1,12 +1,27 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::now;
 use diesel::prelude::*;
 
 use diesel_pagination::{LoadPaginated, PaginationPage};
-use taskrs_db::models::user::{User, UserColumns};
+use taskrs_db::models::auth_refresh_token::AuthRefreshToken;
+use taskrs_db::models::user::{User, UserChangeset, UserColumns};
+use taskrs_db::models::user_invitation::UserInvitation;
+use taskrs_db::token::{generate_token, hash_token};
 use taskrs_db::{Db, DbConnection};
 
+use crate::api::users::{
+    ChangePasswordDto, DeleteUserParams, DeleteUserResult, InviteUserDto, UpdateProfileDto,
+    UpdateUserResult, UserReference,
+};
+use crate::mailer::Message;
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::RestoreEntityParams;
 use crate::models::request_filter::{Order, RequestFilter};
+use crate::models::user_token::{InvitationToken, TokenUser};
+use crate::utils::{
+    bump_token_version, invalidate_token_version_cache, update_permission_cache_for_user,
+};
+use crate::{CONFIG, JWT_KEYS, MAILER, PASSWORD_POLICY};
 
 pub fn get_all_users(
     filter: RequestFilter<UserColumns>,
@@ -27,6 +42,11 @@ pub fn get_all_users(
         );
     }
 
+    // Deleted users are only listed on request
+    if !filter.include_deleted.unwrap_or(false) {
+        db_query = db_query.filter(users::deleted_at.is_null());
+    }
+
     // Order by
     let order_by = filter.order_by.unwrap_or(UserColumns::Email);
     let order = filter.order.unwrap_or(Order::Ascending);
@@ -35,22 +55,22 @@ pub fn get_all_users(
         Order::Ascending => match order_by {
             UserColumns::Id => db_query.order(users::id.asc()),
             UserColumns::Email => db_query.order((users::email.asc(), users::id.asc())),
-            UserColumns::Password => db_query.order((users::password.asc(), users::id.asc())),
             UserColumns::FirstName => db_query.order((users::first_name.asc(), users::id.asc())),
             UserColumns::LastName => db_query.order((users::last_name.asc(), users::id.asc())),
             UserColumns::Activated => db_query.order((users::activated.asc(), users::id.asc())),
             UserColumns::UpdatedAt => db_query.order((users::updated_at.asc(), users::id.asc())),
             UserColumns::CreatedAt => db_query.order((users::created_at.asc(), users::id.asc())),
+            UserColumns::DeletedAt => db_query.order((users::deleted_at.asc(), users::id.asc())),
         },
         Order::Descending => match order_by {
             UserColumns::Id => db_query.order(users::id.desc()),
             UserColumns::Email => db_query.order((users::email.desc(), users::id.asc())),
-            UserColumns::Password => db_query.order((users::password.desc(), users::id.asc())),
             UserColumns::FirstName => db_query.order((users::first_name.desc(), users::id.asc())),
             UserColumns::LastName => db_query.order((users::last_name.desc(), users::id.asc())),
             UserColumns::Activated => db_query.order((users::activated.desc(), users::id.asc())),
             UserColumns::UpdatedAt => db_query.order((users::updated_at.desc(), users::id.asc())),
             UserColumns::CreatedAt => db_query.order((users::created_at.desc(), users::id.asc())),
+            UserColumns::DeletedAt => db_query.order((users::deleted_at.desc(), users::id.asc())),
         },
     };
 
@@ -69,39 +89,384 @@ pub fn create_user(user: User, conn: &PgConnection) -> anyhow::Result<CreateEnti
     Ok(CreateEntityResult::Ok(user.insert(conn)?))
 }
 
+/// Creates a deactivated user and mails an invitation to set a password.
+/// Pending invitations are sent again.
+pub fn invite_user(
+    dto: InviteUserDto,
+    invited_by: i32,
+    conn: &DbConnection,
+) -> anyhow::Result<CreateEntityResult<User>> {
+    use taskrs_db::schema::user_invitations;
+
+    conn.transaction::<_, anyhow::Error, _>(|| {
+        let db_user = match User::find_by_email(&dto.email, conn)? {
+            None => User {
+                email: dto.email,
+                // Random password until the invitation is accepted
+                password: User::hash(&generate_token())?,
+                first_name: dto.first_name,
+                last_name: dto.last_name,
+                activated: false,
+                ..Default::default()
+            }
+            .insert(conn)?,
+            Some(user) => {
+                let pending = user_invitations::table
+                    .filter(user_invitations::user_id.eq(user.id))
+                    .filter(user_invitations::used_at.is_null())
+                    .first::<UserInvitation>(conn)
+                    .optional()?;
+
+                if user.activated || pending.is_none() {
+                    debug!("User '{}' already exists", &user.email);
+                    return Ok(CreateEntityResult::Exists);
+                }
+                user
+            }
+        };
+
+        // Only the latest invitation is valid
+        diesel::delete(
+            user_invitations::table
+                .filter(user_invitations::user_id.eq(db_user.id))
+                .filter(user_invitations::used_at.is_null()),
+        )
+        .execute(conn)?;
+
+        let claims = InvitationToken::new(db_user.id);
+        let token = JWT_KEYS.encode_internal(&claims)?;
+        UserInvitation {
+            user_id: db_user.id,
+            token_hash: hash_token(&token),
+            exp: claims.exp,
+            invited_by: Some(invited_by),
+            ..Default::default()
+        }
+        .insert(conn)?;
+
+        MAILER.send(&Message {
+            to: db_user.email.clone(),
+            subject: "You have been invited to taskrs".to_string(),
+            body: format!(
+                "Use the following token to set your password and activate your account:\n\n{}\n\n\
+                The invitation is valid for {} days.",
+                token,
+                CONFIG.invitation_expiration_time / 86400
+            ),
+        })?;
+
+        Ok(CreateEntityResult::Ok(db_user))
+    })
+}
+
+/// Moves a user to the trash and logs them out everywhere.
+/// Without `cascade` the user must not own or have created projects, be a project member or have tokens.
+/// With `cascade` owned projects are transferred to `transfer_to`
+/// and memberships, tokens and permissions of the user are removed.
 pub fn delete_user(
-    params: DeleteEntityParams,
+    params: DeleteUserParams,
     conn: &DbConnection,
-) -> diesel::QueryResult<DeleteEntityResult<User>> {
+) -> diesel::QueryResult<DeleteUserResult> {
+    use taskrs_db::models::project::NewProjectMember;
+    use taskrs_db::schema::{
+        api_tokens, auth_refresh_tokens, project_members, projects, user_permissions, user_roles,
+        users,
+    };
+
+    let cascade = params.cascade.unwrap_or(false);
+
+    let result = conn.transaction::<DeleteUserResult, diesel::result::Error, _>(|| {
+        let exists = users::table
+            .find(params.id)
+            .filter(users::deleted_at.is_null())
+            .select(users::id)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()?
+            .is_some();
+        if !exists {
+            return Ok(DeleteUserResult::NotFound);
+        }
+
+        // Projects in the trash are transferred as well, so they can be restored
+        let owned_projects: Vec<(i32, String)> = projects::table
+            .filter(projects::owner_id.eq(params.id))
+            .select((projects::id, projects::name))
+            .order(projects::id.asc())
+            .load(conn)?;
+
+        if !cascade {
+            let owned_ids: Vec<i32> = owned_projects.iter().map(|(id, _)| *id).collect();
+            let mut references: Vec<UserReference> = owned_projects
+                .into_iter()
+                .map(|(id, name)| UserReference::OwnedProject { id, name })
+                .collect();
+
+            references.extend(
+                projects::table
+                    .filter(projects::creator_id.eq(params.id))
+                    .filter(projects::owner_id.ne(params.id))
+                    .select((projects::id, projects::name))
+                    .order(projects::id.asc())
+                    .load::<(i32, String)>(conn)?
+                    .into_iter()
+                    .map(|(id, name)| UserReference::CreatedProject { id, name }),
+            );
+            references.extend(
+                project_members::table
+                    .filter(project_members::user_id.eq(params.id))
+                    .filter(project_members::project_id.ne_all(owned_ids))
+                    .select(project_members::project_id)
+                    .order(project_members::project_id.asc())
+                    .load::<i32>(conn)?
+                    .into_iter()
+                    .map(|project_id| UserReference::ProjectMember { project_id }),
+            );
+            references.extend(
+                auth_refresh_tokens::table
+                    .filter(auth_refresh_tokens::user_id.eq(params.id))
+                    .filter(auth_refresh_tokens::exp.gt(Utc::now().timestamp()))
+                    .select(auth_refresh_tokens::id)
+                    .order(auth_refresh_tokens::id.asc())
+                    .load::<i32>(conn)?
+                    .into_iter()
+                    .map(|id| UserReference::RefreshToken { id }),
+            );
+            references.extend(
+                api_tokens::table
+                    .filter(api_tokens::user_id.eq(params.id))
+                    .select((api_tokens::id, api_tokens::name))
+                    .order(api_tokens::id.asc())
+                    .load::<(i32, String)>(conn)?
+                    .into_iter()
+                    .map(|(id, name)| UserReference::ApiToken { id, name }),
+            );
+
+            if !references.is_empty() {
+                return Ok(DeleteUserResult::Referenced(references));
+            }
+        } else {
+            if !owned_projects.is_empty() {
+                let new_owner = match params.transfer_to {
+                    Some(id) if id != params.id => users::table
+                        .find(id)
+                        .filter(users::deleted_at.is_null())
+                        .select(users::id)
+                        .first::<i32>(conn)
+                        .optional()?,
+                    _ => None,
+                };
+                let new_owner = match new_owner {
+                    None => return Ok(DeleteUserResult::InvalidTransferUser),
+                    Some(id) => id,
+                };
+
+                debug!(
+                    "Transferring {} projects of user {} to user {}",
+                    owned_projects.len(),
+                    params.id,
+                    new_owner
+                );
+                diesel::update(projects::table.filter(projects::owner_id.eq(params.id)))
+                    .set(projects::owner_id.eq(new_owner))
+                    .execute(conn)?;
+
+                // The new owner is an admin of the transferred projects
+                for (project_id, _) in &owned_projects {
+                    diesel::insert_into(project_members::table)
+                        .values(NewProjectMember {
+                            project_id: *project_id,
+                            user_id: new_owner,
+                            is_admin: true,
+                        })
+                        .on_conflict((project_members::project_id, project_members::user_id))
+                        .do_update()
+                        .set(project_members::is_admin.eq(true))
+                        .execute(conn)?;
+                }
+            }
+
+            diesel::delete(project_members::table.filter(project_members::user_id.eq(params.id)))
+                .execute(conn)?;
+            diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(params.id)))
+                .execute(conn)?;
+            diesel::delete(user_permissions::table.filter(user_permissions::user_id.eq(params.id)))
+                .execute(conn)?;
+            diesel::delete(user_roles::table.filter(user_roles::user_id.eq(params.id)))
+                .execute(conn)?;
+        }
+
+        diesel::update(users::table.find(params.id))
+            .set(users::deleted_at.eq(now.nullable()))
+            .execute(conn)?;
+        diesel::delete(
+            auth_refresh_tokens::table.filter(auth_refresh_tokens::user_id.eq(params.id)),
+        )
+        .execute(conn)?;
+        bump_token_version(params.id, conn)?;
+
+        Ok(DeleteUserResult::Ok)
+    })?;
+
+    // Permissions were removed
+    if cascade && matches!(result, DeleteUserResult::Ok) {
+        update_permission_cache_for_user(params.id, conn)?;
+    }
+
+    Ok(result)
+}
+
+/// Restores a user from the trash
+pub fn restore_user(
+    params: RestoreEntityParams,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<User>> {
     use taskrs_db::schema::users;
 
-    let count = diesel::delete(users::table.filter(users::id.eq(params.id))).execute(conn)?;
+    let target = users::table
+        .find(params.id)
+        .filter(users::deleted_at.is_not_null());
+    diesel::update(target)
+        .set(users::deleted_at.eq(None::<NaiveDateTime>))
+        .get_result::<User>(conn)
+        .optional()
+}
+
+/// Updates the supplied fields of a user, a new password is hashed
+pub fn update_user(
+    changes: UserChangeset,
+    conn: &DbConnection,
+) -> anyhow::Result<UpdateUserResult> {
+    use taskrs_db::schema::{auth_refresh_tokens, users};
 
-    if count > 0 {
-        Ok(DeleteEntityResult::Ok)
-    } else {
-        Ok(DeleteEntityResult::NotFound)
+    let db_user = match users::table
+        .find(changes.id)
+        .filter(users::deleted_at.is_null())
+        .first::<User>(conn)
+        .optional()?
+    {
+        None => return Ok(UpdateUserResult::NotFound),
+        Some(user) => user,
+    };
+
+    if changes.is_empty() {
+        return Ok(UpdateUserResult::Ok(db_user));
+    }
+
+    let mut changes = changes;
+    if let Some(password) = &changes.password {
+        let email = changes.email.as_deref().unwrap_or(&db_user.email);
+        let violations = PASSWORD_POLICY.validate(password, email);
+        if !violations.is_empty() {
+            return Ok(UpdateUserResult::PolicyViolated(violations));
+        }
+        changes.password = Some(User::hash(password)?);
     }
+
+    // Issued tokens become invalid if the user is deactivated or the credentials change
+    let revoke_tokens = !changes.activated.unwrap_or(db_user.activated)
+        || changes.email.iter().any(|email| email != &db_user.email)
+        || changes.password.is_some();
+
+    let user = diesel::update(users::table.find(db_user.id))
+        .set(&changes)
+        .get_result::<User>(conn)?;
+
+    if revoke_tokens {
+        bump_token_version(db_user.id, conn)?;
+
+        // Sign out everywhere, refresh tokens would issue new access tokens otherwise
+        diesel::delete(
+            auth_refresh_tokens::table.filter(auth_refresh_tokens::user_id.eq(db_user.id)),
+        )
+        .execute(conn)?;
+    }
+
+    Ok(UpdateUserResult::Ok(user))
 }
 
-pub fn update_user(user: User, conn: &DbConnection) -> anyhow::Result<Option<User>> {
+pub fn get_current_user(
+    user_id: i32,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<TokenUser>> {
     use taskrs_db::schema::users;
 
-    // hash password
-    let mut user = user;
-    user.hash_password()?;
+    let user = users::table.find(user_id).first::<User>(conn).optional()?;
 
-    let target = users::table.find(user.id);
-    let user = diesel::update(target)
-        .set((
-            users::email.eq(user.email),
-            users::password.eq(user.password),
-            users::first_name.eq(user.first_name),
-            users::last_name.eq(user.last_name),
-            users::activated.eq(user.activated),
-        ))
-        .get_result::<User>(conn)
-        .optional()?;
+    Ok(user.map(|user| user.into()))
+}
+
+pub fn update_current_user(
+    user_id: i32,
+    profile: UpdateProfileDto,
+    conn: &DbConnection,
+) -> diesel::QueryResult<Option<TokenUser>> {
+    use taskrs_db::schema::users;
+
+    let changes = profile.into_changeset(user_id);
+    let target = users::table
+        .find(user_id)
+        .filter(users::deleted_at.is_null());
+
+    // Only change supplied fields
+    let user = if changes.is_empty() {
+        target.first::<User>(conn).optional()?
+    } else {
+        diesel::update(target)
+            .set(&changes)
+            .get_result::<User>(conn)
+            .optional()?
+    };
+
+    Ok(user.map(|user| user.into()))
+}
+
+/// Changes the password of a user and signs out all other sessions.
+/// Returns `false` if the current password does not match.
+pub fn change_password(
+    user_id: i32,
+    passwords: ChangePasswordDto,
+    conn: &DbConnection,
+) -> anyhow::Result<bool> {
+    use taskrs_db::schema::{auth_refresh_tokens, users};
+
+    let db_user = match users::table.find(user_id).first::<User>(conn).optional()? {
+        None => return Ok(false),
+        Some(user) => user,
+    };
+
+    let matches = argon2::verify_encoded(&db_user.password, passwords.current_password.as_bytes())?;
+    if !matches {
+        debug!("Wrong password");
+        return Ok(false);
+    }
+
+    // Only a session of the user can be kept
+    let current_family = match &passwords.refresh_token {
+        Some(refresh_token) => AuthRefreshToken::find(refresh_token, conn)?
+            .filter(|token| token.user_id == user_id)
+            .map(|token| token.family),
+        None => None,
+    };
+
+    let password = User::hash(&passwords.new_password)?;
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(users::table.find(user_id))
+            .set(users::password.eq(password))
+            .execute(conn)?;
+        bump_token_version(user_id, conn)?;
+
+        // Sign out everywhere else
+        let mut query = diesel::delete(auth_refresh_tokens::table)
+            .filter(auth_refresh_tokens::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(family) = current_family {
+            query = query.filter(auth_refresh_tokens::family.ne(family));
+        }
+        query.execute(conn)
+    })?;
+
+    invalidate_token_version_cache(user_id);
 
-    Ok(user)
+    Ok(true)
 }
@@ -1,13 +1,18 @@
 use actix_web::web;
-use actix_web::{delete, get, post, put, HttpResponse};
+use actix_web::{delete, get, patch, post, put, HttpResponse};
 
-use taskrs_db::models::user::{User, UserColumns};
+use taskrs_db::models::user::{User, UserChangeset, UserColumns};
 use taskrs_db::DbPool;
 
+use crate::api::users::{
+    ChangePasswordDto, DeleteUserParams, DeleteUserResult, InviteUserDto, UpdateProfileDto,
+    UpdateUserDto, UpdateUserResult,
+};
 use crate::models::create_entity_result::CreateEntityResult;
-use crate::models::delete_entity::{DeleteEntityParams, DeleteEntityResult};
+use crate::models::delete_entity::RestoreEntityParams;
+use crate::models::entity_version::{self, IfMatch, IfMatchResult};
 use crate::models::request_filter::RequestFilter;
-use crate::models::user_token::TokenUser;
+use crate::models::user_token::{JwtUser, TokenUser};
 use crate::permissions;
 use crate::utils;
 
@@ -54,6 +59,8 @@ pub async fn create_user(
     // Check permission
     utils::has_permission(&user, &permissions::USER_CREATE, &conn)?;
 
+    utils::check_password_policy(&new_user.password, &new_user.email)?;
+
     // Create user
     web::block(move || actions::create_user(new_user, &conn))
         .await
@@ -69,14 +76,45 @@ pub async fn create_user(
         })
 }
 
-/// Delete a user
+/// Invites a user by mail, the account is activated once the invitee sets a password
+///
+/// Permission: `user_create`
+#[post("/invite")]
+pub async fn invite_user(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    dto: web::Json<InviteUserDto>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let dto = dto.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::USER_CREATE, &conn)?;
+
+    // Invite user
+    web::block(move || actions::invite_user(dto, user.id, &conn))
+        .await
+        .map(|invited_user| match invited_user {
+            CreateEntityResult::Ok(user) => HttpResponse::Created().json(user),
+            CreateEntityResult::Exists => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
+
+/// Delete a user, with `cascade` their projects are transferred to `transferTo`
 ///
 /// Permission: `user_delete`
 #[delete("")]
 pub async fn delete_user(
-    params: web::Query<DeleteEntityParams>,
+    params: web::Query<DeleteUserParams>,
     user: TokenUser,
     pool: web::Data<DbPool>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let params = params.into_inner();
@@ -85,14 +123,57 @@ pub async fn delete_user(
     utils::has_permission(&user, &permissions::USER_DELETE, &conn)?;
 
     // Delete user
-    web::block(move || actions::delete_user(params, &conn))
+    web::block(move || {
+        let user_id = params.id;
+        let result = entity_version::if_match::<User, _, _, _>(user_id, &if_match, &conn, || {
+            actions::delete_user(params, &conn)
+        });
+
+        // The token version may have changed
+        utils::invalidate_token_version_cache(user_id);
+        result
+    })
+    .await
+    .map(|result| match result {
+        IfMatchResult::Ok(DeleteUserResult::Ok) => HttpResponse::Ok().finish(),
+        IfMatchResult::Ok(DeleteUserResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(DeleteUserResult::Referenced(references)) => {
+            HttpResponse::BadRequest().json(references)
+        }
+        IfMatchResult::Ok(DeleteUserResult::InvalidTransferUser) => {
+            HttpResponse::BadRequest().finish()
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Restore a user from the trash
+///
+/// Permission: `user_restore`
+#[post("/restore")]
+pub async fn restore_user(
+    params: web::Query<RestoreEntityParams>,
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let params = params.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::USER_RESTORE, &conn)?;
+
+    // Restore user
+    web::block(move || actions::restore_user(params, &conn))
         .await
-        .map(|result| match result {
-            DeleteEntityResult::Ok => HttpResponse::Ok().finish(),
-            DeleteEntityResult::NotFound => HttpResponse::NotFound().finish(),
-            DeleteEntityResult::Referenced(references) => {
-                HttpResponse::BadRequest().json(references)
-            }
+        .map(|restored_user| match restored_user {
+            Some(user) => entity_version::versioned_response(user),
+            None => HttpResponse::NotFound().finish(),
         })
         .map_err(|e| {
             error!("{}", e);
@@ -109,7 +190,52 @@ pub async fn delete_user(
 pub async fn update_user(
     user: TokenUser,
     pool: web::Data<DbPool>,
-    updated_user: web::Json<User>,
+    if_match: IfMatch,
+    updated_user: web::Json<UpdateUserDto>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let updated_user = updated_user.into_inner();
+
+    // Check permission
+    utils::has_permission(&user, &permissions::USER_UPDATE, &conn)?;
+
+    // Update user
+    web::block(move || {
+        let user_id = updated_user.id;
+        let result = entity_version::if_match::<User, _, _, _>(user_id, &if_match, &conn, || {
+            actions::update_user(updated_user.into(), &conn)
+        });
+
+        // The token version may have changed
+        utils::invalidate_token_version_cache(user_id);
+        result
+    })
+    .await
+    .map(|updated_user| match updated_user {
+        IfMatchResult::Ok(UpdateUserResult::Ok(user)) => entity_version::versioned_response(user),
+        IfMatchResult::Ok(UpdateUserResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(UpdateUserResult::PolicyViolated(violations)) => {
+            HttpResponse::BadRequest().json(violations)
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Update the supplied fields of a user, `null` clears a name
+///
+/// Permission: `user_update`
+#[patch("")]
+pub async fn patch_user(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
+    updated_user: web::Json<UserChangeset>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let conn = utils::get_db_connection(pool.into_inner())?;
     let updated_user = updated_user.into_inner();
@@ -118,10 +244,47 @@ pub async fn update_user(
     utils::has_permission(&user, &permissions::USER_UPDATE, &conn)?;
 
     // Update user
-    web::block(move || actions::update_user(updated_user, &conn))
+    web::block(move || {
+        let user_id = updated_user.id;
+        let result = entity_version::if_match::<User, _, _, _>(user_id, &if_match, &conn, || {
+            actions::update_user(updated_user, &conn)
+        });
+
+        // The token version may have changed
+        utils::invalidate_token_version_cache(user_id);
+        result
+    })
+    .await
+    .map(|updated_user| match updated_user {
+        IfMatchResult::Ok(UpdateUserResult::Ok(user)) => entity_version::versioned_response(user),
+        IfMatchResult::Ok(UpdateUserResult::NotFound) => HttpResponse::NotFound().finish(),
+        IfMatchResult::Ok(UpdateUserResult::PolicyViolated(violations)) => {
+            HttpResponse::BadRequest().json(violations)
+        }
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Returns the current user
+///
+/// Permission: none
+#[get("/me")]
+pub async fn current_user(
+    user: TokenUser,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+
+    web::block(move || actions::get_current_user(user.id, &conn))
         .await
-        .map(|updated_user| match updated_user {
-            Some(user) => HttpResponse::Ok().json(user),
+        .map(|current_user| match current_user {
+            Some(user) => entity_version::versioned_response(user),
             None => HttpResponse::NotFound().finish(),
         })
         .map_err(|e| {
@@ -131,3 +294,64 @@ pub async fn update_user(
                 .into()
         })
 }
+
+/// Update the name of the current user
+///
+/// Permission: none
+#[patch("/me")]
+pub async fn update_current_user(
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+    if_match: IfMatch,
+    profile: web::Json<UpdateProfileDto>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let profile = profile.into_inner();
+
+    web::block(move || {
+        entity_version::if_match::<TokenUser, _, _, _>(user.id, &if_match, &conn, || {
+            actions::update_current_user(user.id, profile, &conn)
+        })
+    })
+    .await
+    .map(|updated_user| match updated_user {
+        IfMatchResult::Ok(Some(user)) => entity_version::versioned_response(user),
+        IfMatchResult::Ok(None) => HttpResponse::NotFound().finish(),
+        IfMatchResult::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+    })
+    .map_err(|e| {
+        error!("{}", e);
+        HttpResponse::InternalServerError()
+            .body(e.to_string())
+            .into()
+    })
+}
+
+/// Change the password of the current user. Requires the current password.
+/// Signs out all sessions except the one of the supplied refresh token.
+///
+/// Permission: none
+#[put("/me/password")]
+pub async fn change_password(
+    user: JwtUser,
+    pool: web::Data<DbPool>,
+    passwords: web::Json<ChangePasswordDto>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = utils::get_db_connection(pool.into_inner())?;
+    let passwords = passwords.into_inner();
+
+    utils::check_password_policy(&passwords.new_password, &user.email)?;
+
+    web::block(move || actions::change_password(user.id, passwords, &conn))
+        .await
+        .map(|changed| match changed {
+            true => HttpResponse::Ok().finish(),
+            false => HttpResponse::BadRequest().finish(),
+        })
+        .map_err(|e| {
+            error!("{}", e);
+            HttpResponse::InternalServerError()
+                .body(e.to_string())
+                .into()
+        })
+}
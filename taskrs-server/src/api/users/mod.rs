@@ -1,4 +1,9 @@
 use actix_web::{web, Scope};
+use serde::{Deserialize, Serialize};
+use taskrs_db::models::double_option;
+use taskrs_db::models::user::{User, UserChangeset};
+
+use crate::password_policy::PasswordViolation;
 
 mod actions;
 mod controller;
@@ -6,14 +11,137 @@ mod controller;
 pub fn register(scope: Scope) -> Scope {
     let mut user_scope = web::scope("users").wrap(crate::middleware::auth::Authentication);
 
-    // Debug routes
-    if cfg!(debug_assertions) {}
-
     user_scope = user_scope
         .service(controller::all_users)
         .service(controller::create_user)
+        .service(controller::invite_user)
         .service(controller::delete_user)
-        .service(controller::update_user);
+        .service(controller::restore_user)
+        .service(controller::update_user)
+        .service(controller::patch_user)
+        .service(controller::current_user)
+        .service(controller::update_current_user)
+        .service(controller::change_password);
 
     scope.service(user_scope)
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileDto {
+    #[serde(default, deserialize_with = "double_option")]
+    pub first_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub last_name: Option<Option<String>>,
+}
+
+impl UpdateProfileDto {
+    pub fn into_changeset(self, user_id: i32) -> UserChangeset {
+        UserChangeset {
+            id: user_id,
+            first_name: self.first_name,
+            last_name: self.last_name,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordDto {
+    pub current_password: String,
+    pub new_password: String,
+    /// Refresh token of the session that stays signed in
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteUserDto {
+    pub email: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+}
+
+/// Fields that are not supplied stay unchanged, names can not be cleared
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserDto {
+    pub id: i32,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub activated: Option<bool>,
+}
+
+impl From<UpdateUserDto> for UserChangeset {
+    fn from(
+        UpdateUserDto {
+            id,
+            email,
+            password,
+            first_name,
+            last_name,
+            activated,
+        }: UpdateUserDto,
+    ) -> Self {
+        Self {
+            id,
+            email,
+            password,
+            first_name: first_name.map(Some),
+            last_name: last_name.map(Some),
+            activated,
+        }
+    }
+}
+
+pub enum UpdateUserResult {
+    Ok(User),
+    NotFound,
+    PolicyViolated(Vec<PasswordViolation>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteUserParams {
+    pub id: i32,
+    #[serde(alias = "c")]
+    pub cascade: Option<bool>,
+    /// New owner of the projects of the deleted user
+    pub transfer_to: Option<i32>,
+}
+
+/// Keeps a user from being deleted without `cascade`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UserReference {
+    OwnedProject {
+        id: i32,
+        name: String,
+    },
+    CreatedProject {
+        id: i32,
+        name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ProjectMember {
+        project_id: i32,
+    },
+    RefreshToken {
+        id: i32,
+    },
+    ApiToken {
+        id: i32,
+        name: String,
+    },
+}
+
+pub enum DeleteUserResult {
+    Ok,
+    NotFound,
+    Referenced(Vec<UserReference>),
+    InvalidTransferUser,
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use diesel::prelude::*;
+use ldap3::{ldap_escape, LdapConn, LdapConnSettings, Scope, SearchEntry};
+
+use taskrs_db::models::user::User;
+use taskrs_db::token::generate_token;
+use taskrs_db::DbConnection;
+
+use crate::config::{Authenticator as AuthenticatorConfig, Config, Ldap};
+
+/// Verifies the credentials of a login
+pub trait Authenticator: Send + Sync {
+    /// Returns the user if email and password are valid
+    fn authenticate(
+        &self,
+        email: &str,
+        password: &str,
+        conn: &DbConnection,
+    ) -> anyhow::Result<Option<User>>;
+}
+
+/// Verifies passwords against the argon2 hashes in `users`
+pub struct LocalAuthenticator;
+
+impl Authenticator for LocalAuthenticator {
+    fn authenticate(
+        &self,
+        email: &str,
+        password: &str,
+        conn: &DbConnection,
+    ) -> anyhow::Result<Option<User>> {
+        let db_user = match User::find_by_email(email, conn)? {
+            None => return Ok(None),
+            Some(user) => user,
+        };
+
+        if !argon2::verify_encoded(&db_user.password, password.as_bytes())? {
+            debug!("Wrong password");
+            return Ok(None);
+        }
+
+        Ok(Some(db_user))
+    }
+}
+
+/// Verifies passwords by binding to an LDAP directory as the user
+pub struct LdapAuthenticator {
+    config: Ldap,
+}
+
+impl Authenticator for LdapAuthenticator {
+    fn authenticate(
+        &self,
+        email: &str,
+        password: &str,
+        conn: &DbConnection,
+    ) -> anyhow::Result<Option<User>> {
+        use taskrs_db::schema::users;
+
+        // An empty password would be an unauthenticated bind that always succeeds
+        if password.is_empty() {
+            return Ok(None);
+        }
+
+        let mut ldap = LdapConn::with_settings(
+            LdapConnSettings::new()
+                .set_conn_timeout(Duration::from_secs(self.config.timeout as u64)),
+            &self.config.url,
+        )?;
+        ldap.with_timeout(Duration::from_secs(self.config.timeout as u64));
+
+        // Search user with the service account
+        ldap.simple_bind(&self.config.bind_dn, &self.config.bind_password)?
+            .success()?;
+        let filter = self
+            .config
+            .user_filter
+            .replace("{email}", &ldap_escape(email));
+        let (entries, _) = ldap
+            .search(
+                &self.config.base_dn,
+                Scope::Subtree,
+                &filter,
+                vec![
+                    &self.config.first_name_attribute,
+                    &self.config.last_name_attribute,
+                ],
+            )?
+            .success()?;
+
+        let entry = match entries.len() {
+            1 => SearchEntry::construct(entries.into_iter().next().unwrap()),
+            0 => {
+                debug!("No LDAP entry for '{}'", email);
+                return Ok(None);
+            }
+            _ => {
+                warn!("Multiple LDAP entries for '{}'", email);
+                return Ok(None);
+            }
+        };
+
+        // Bind as the user to verify the password
+        let result = ldap.simple_bind(&entry.dn, password)?;
+        let _ = ldap.unbind();
+        if result.rc != 0 {
+            debug!("LDAP bind of '{}' failed: {}", &entry.dn, result);
+            return Ok(None);
+        }
+
+        let attribute = |name: &str| {
+            entry
+                .attrs
+                .get(name)
+                .and_then(|values| values.first())
+                .cloned()
+        };
+        let first_name = attribute(&self.config.first_name_attribute);
+        let last_name = attribute(&self.config.last_name_attribute);
+
+        match User::find_by_email(email, conn)? {
+            // Sync names from the directory
+            Some(db_user) => Ok(Some(
+                diesel::update(users::table.find(db_user.id))
+                    .set((
+                        users::first_name.eq(first_name),
+                        users::last_name.eq(last_name),
+                    ))
+                    .get_result::<User>(conn)?,
+            )),
+            None if self.config.auto_provision => {
+                info!("Provisioning user '{}' from LDAP", email);
+                Ok(Some(
+                    User {
+                        email: email.to_string(),
+                        // Random password, the user logs in through LDAP
+                        password: User::hash(&generate_token())?,
+                        first_name,
+                        last_name,
+                        activated: true,
+                        ..Default::default()
+                    }
+                    .insert(conn)?,
+                ))
+            }
+            None => {
+                debug!("No user with email '{}'", email);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Creates the authenticators configured in `Config`
+pub fn from_config(config: &Config) -> Vec<Box<dyn Authenticator>> {
+    config
+        .authenticators
+        .iter()
+        .map(|authenticator| -> Box<dyn Authenticator> {
+            match authenticator {
+                AuthenticatorConfig::Local => Box::new(LocalAuthenticator),
+                AuthenticatorConfig::Ldap => Box::new(LdapAuthenticator {
+                    config: config.ldap.clone(),
+                }),
+            }
+        })
+        .collect()
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use taskrs_db::models::role::RoleSeed;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Database {
     pub user: String,
@@ -14,23 +16,149 @@ pub struct Storage {
     pub images: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    Log,
+    File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mail {
+    pub transport: MailTransport,
+    pub sender: String,
+    pub directory: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Authenticator {
+    Local,
+    Ldap,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ldap {
+    pub url: String,
+    /// Account used to search users, empty binds anonymously
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub base_dn: String,
+    /// Filter finding the user, `{email}` is replaced with the escaped login email
+    pub user_filter: String,
+    pub first_name_attribute: String,
+    pub last_name_attribute: String,
+    /// Create users that exist in the directory but not yet in taskrs
+    pub auto_provision: bool,
+    pub timeout: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    pub min_length: u32,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    /// Deny common passwords
+    pub deny_common: bool,
+    /// File with additional denied passwords, one per line
+    pub deny_list: String,
+    /// Deny passwords equal to the email
+    pub deny_email: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginProtection {
+    /// Failed logins per email before it is locked, 0 disables it
+    pub max_failures: u32,
+    /// Failed logins per client IP before it is locked, 0 disables it
+    pub max_ip_failures: u32,
+    /// Seconds after which failures are forgotten
+    pub failure_window: u32,
+    /// Seconds of the first lockout, doubled with every further failure
+    pub lockout_time: u32,
+    pub max_lockout_time: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtKey {
+    /// Key id put into the `kid` header
+    pub kid: String,
+    pub algorithm: jsonwebtoken::Algorithm,
+    /// PEM file of the public key
+    pub public_key: String,
+    /// PEM file of the private key, only needed for the signing key
+    pub private_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwt {
+    /// Key id used to sign access tokens, empty signs them with `access_token_secret`
+    pub signing_key: String,
+    /// Keys accepted for verification, keep retired keys here until their tokens expired
+    #[serde(default)]
+    pub keys: Vec<JwtKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Oidc {
+    /// Issuer URL of the identity provider, empty disables OpenID Connect login
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Page the provider sends the user back to, it has to pass `code` and `state` to the callback
+    pub redirect_uri: String,
+    pub scopes: String,
+    /// Create users logging in for the first time, otherwise the email has to exist
+    pub auto_provision: bool,
+    /// Link identities to existing users with the same verified email.
+    /// Only enable it if the provider can be trusted to verify emails.
+    #[serde(default)]
+    pub link_existing_users: bool,
+    pub state_expiration_time: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiServer {
     pub address: String,
     pub port: u16,
+    /// Proxies whose `X-Forwarded-For` header is trusted for the client IP
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub access_token_secret: String,
     pub refresh_token_secret: String,
+    pub jwt: Jwt,
     pub access_token_expiration_time: u32,
     pub refresh_token_expiration_time: u32,
+    pub password_reset_token_expiration_time: u32,
+    pub invitation_expiration_time: u32,
+    pub totp_challenge_expiration_time: u32,
+    /// Issuer shown in authenticator apps
+    pub totp_issuer: String,
+    /// Seconds between maintenance runs, 0 disables them
+    pub maintenance_interval: u32,
+    /// Seconds deleted users, projects and categories are kept before they are purged
+    pub deleted_retention_time: u32,
     pub root_user_email: String,
     pub root_user_password: String,
     pub seed_root_permissions: bool,
+    /// Roles seeded on startup
+    #[serde(default)]
+    pub roles: Vec<RoleSeed>,
     pub database: Database,
     pub storage: Storage,
+    pub mail: Mail,
+    pub login_protection: LoginProtection,
+    pub password_policy: PasswordPolicy,
+    /// Backends that verify passwords on login, tried in order
+    pub authenticators: Vec<Authenticator>,
+    pub ldap: Ldap,
+    pub oidc: Oidc,
     pub server: ApiServer,
 }
 
@@ -61,11 +189,22 @@ impl Default for Config {
         Self {
             access_token_secret: "secret".to_string(),
             refresh_token_secret: "secret".to_string(),
+            jwt: Jwt {
+                signing_key: "".to_string(),
+                keys: vec![],
+            },
             access_token_expiration_time: 3600,
             refresh_token_expiration_time: 31536000,
+            password_reset_token_expiration_time: 3600,
+            invitation_expiration_time: 604800,
+            totp_challenge_expiration_time: 300,
+            totp_issuer: "taskrs".to_string(),
+            maintenance_interval: 3600,
+            deleted_retention_time: 2592000,
             root_user_email: "root@taskrs.com".to_string(),
             root_user_password: "root".to_string(),
             seed_root_permissions: false,
+            roles: vec![],
             database: Database {
                 user: "postgres".to_string(),
                 password: "password".to_string(),
@@ -76,9 +215,54 @@ impl Default for Config {
             storage: Storage {
                 images: "storage/images".to_string(),
             },
+            mail: Mail {
+                transport: MailTransport::Log,
+                sender: "noreply@taskrs.com".to_string(),
+                directory: "storage/mails".to_string(),
+            },
+            login_protection: LoginProtection {
+                max_failures: 5,
+                max_ip_failures: 20,
+                failure_window: 900,
+                lockout_time: 300,
+                max_lockout_time: 86400,
+            },
+            password_policy: PasswordPolicy {
+                min_length: 8,
+                require_lowercase: true,
+                require_uppercase: true,
+                require_digit: true,
+                require_special: false,
+                deny_common: true,
+                deny_list: "".to_string(),
+                deny_email: true,
+            },
+            authenticators: vec![Authenticator::Local],
+            ldap: Ldap {
+                url: "ldap://localhost:389".to_string(),
+                bind_dn: "".to_string(),
+                bind_password: "".to_string(),
+                base_dn: "".to_string(),
+                user_filter: "(mail={email})".to_string(),
+                first_name_attribute: "givenName".to_string(),
+                last_name_attribute: "sn".to_string(),
+                auto_provision: false,
+                timeout: 10,
+            },
+            oidc: Oidc {
+                issuer: "".to_string(),
+                client_id: "".to_string(),
+                client_secret: "".to_string(),
+                redirect_uri: "".to_string(),
+                scopes: "openid email profile".to_string(),
+                auto_provision: false,
+                link_existing_users: false,
+                state_expiration_time: 600,
+            },
             server: ApiServer {
                 address: "0.0.0.0".to_string(),
                 port: 8080,
+                trusted_proxies: vec![],
             },
         }
     }
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::config::{Config, JwtKey};
+
+// DER tags needed to read public keys
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const SEQUENCE: u8 = 0x30;
+
+/// Claims of tokens only read by this server, like challenges and invitations.
+/// The audience tells them apart, so one kind can't be used as another.
+pub trait InternalClaims: Serialize + DeserializeOwned {
+    const AUDIENCE: &'static str;
+}
+
+/// Keys used to sign and verify tokens, built once at startup
+pub struct JwtKeys {
+    header: Header,
+    encoding_key: EncodingKey,
+    /// Verification keys by `kid`, tokens without `kid` are signed with `access_token_secret`
+    decoding_keys: HashMap<Option<String>, (DecodingKey, Validation)>,
+    refresh_encoding_key: EncodingKey,
+    refresh_decoding_key: DecodingKey,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = JwkSet { keys: vec![] };
+
+        for key in &config.jwt.keys {
+            let public_key = std::fs::read(&key.public_key)?;
+            let decoding_key = match key.algorithm {
+                Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::PS384
+                | Algorithm::PS512 => DecodingKey::from_rsa_pem(&public_key)?,
+                Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(&public_key)?,
+                Algorithm::EdDSA => DecodingKey::from_ed_pem(&public_key)?,
+                _ => bail!(
+                    "Algorithm {:?} of key '{}' is not supported",
+                    key.algorithm,
+                    key.kid
+                ),
+            };
+
+            decoding_keys.insert(
+                Some(key.kid.clone()),
+                (decoding_key, Validation::new(key.algorithm)),
+            );
+            jwks.keys.push(jwk(key, &public_key)?);
+        }
+
+        let (header, encoding_key) = if config.jwt.signing_key.is_empty() {
+            decoding_keys.insert(
+                None,
+                (
+                    DecodingKey::from_secret(config.access_token_secret.as_bytes()),
+                    Validation::default(),
+                ),
+            );
+            (
+                Header::default(),
+                EncodingKey::from_secret(config.access_token_secret.as_bytes()),
+            )
+        } else {
+            let key = config
+                .jwt
+                .keys
+                .iter()
+                .find(|key| key.kid == config.jwt.signing_key)
+                .ok_or_else(|| anyhow!("Signing key '{}' not found", config.jwt.signing_key))?;
+            let private_key = match &key.private_key {
+                Some(path) => std::fs::read(path)?,
+                None => bail!("Signing key '{}' has no private key", key.kid),
+            };
+            let encoding_key = match key.algorithm {
+                Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&private_key)?,
+                Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_key)?,
+                _ => EncodingKey::from_rsa_pem(&private_key)?,
+            };
+
+            let mut header = Header::new(key.algorithm);
+            header.kid = Some(key.kid.clone());
+            (header, encoding_key)
+        };
+
+        Ok(Self {
+            header,
+            encoding_key,
+            decoding_keys,
+            refresh_encoding_key: EncodingKey::from_secret(config.refresh_token_secret.as_bytes()),
+            refresh_decoding_key: DecodingKey::from_secret(config.refresh_token_secret.as_bytes()),
+            jwks,
+        })
+    }
+
+    /// Signs claims with the current signing key
+    pub fn encode<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        jsonwebtoken::encode(&self.header, claims, &self.encoding_key)
+    }
+
+    /// Decodes and validates a token with the key named in its `kid` header
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> jsonwebtoken::errors::Result<TokenData<T>> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let (key, validation) = self
+            .decoding_keys
+            .get(&header.kid)
+            .ok_or(ErrorKind::InvalidSignature)?;
+
+        jsonwebtoken::decode(token, key, validation)
+    }
+
+    /// Refresh tokens are only read by this server and stay HMAC signed
+    pub fn encode_refresh<T: Serialize>(&self, claims: &T) -> jsonwebtoken::errors::Result<String> {
+        jsonwebtoken::encode(&Header::default(), claims, &self.refresh_encoding_key)
+    }
+
+    pub fn decode_refresh<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> jsonwebtoken::errors::Result<TokenData<T>> {
+        jsonwebtoken::decode(token, &self.refresh_decoding_key, &Validation::default())
+    }
+
+    /// Internal tokens are HMAC signed like refresh tokens, the published keys only sign access tokens
+    pub fn encode_internal<T: InternalClaims>(
+        &self,
+        claims: &T,
+    ) -> jsonwebtoken::errors::Result<String> {
+        jsonwebtoken::encode(&Header::default(), claims, &self.refresh_encoding_key)
+    }
+
+    /// Decodes an internal token, fails if it was issued for another purpose
+    pub fn decode_internal<T: InternalClaims>(
+        &self,
+        token: &str,
+    ) -> jsonwebtoken::errors::Result<TokenData<T>> {
+        let mut validation = Validation::default();
+        validation.set_required_spec_claims(&["exp", "aud"]);
+        validation.set_audience(&[T::AUDIENCE]);
+
+        jsonwebtoken::decode(token, &self.refresh_decoding_key, &validation)
+    }
+
+    /// Public keys for other services to verify access tokens
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// Builds the JWK of a PEM encoded public key
+fn jwk(key: &JwtKey, public_key: &[u8]) -> anyhow::Result<Jwk> {
+    let invalid = || anyhow!("Public key of '{}' is invalid", key.kid);
+    let pem = pem::parse(public_key)?;
+
+    let algorithm = match key.algorithm {
+        Algorithm::EdDSA => {
+            let x = subject_public_key(&pem.contents).ok_or_else(invalid)?;
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: base64_url(x),
+            })
+        }
+        Algorithm::ES256 | Algorithm::ES384 => {
+            // Uncompressed point: 0x04 followed by both coordinates
+            let point = subject_public_key(&pem.contents)
+                .and_then(|point| point.split_first())
+                .filter(|(format, _)| **format == 0x04)
+                .map(|(_, coordinates)| coordinates)
+                .ok_or_else(invalid)?;
+            let (x, y) = point.split_at(point.len() / 2);
+            AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: match key.algorithm {
+                    Algorithm::ES256 => EllipticCurve::P256,
+                    _ => EllipticCurve::P384,
+                },
+                x: base64_url(x),
+                y: base64_url(y),
+            })
+        }
+        _ => {
+            // PKCS#1 keys contain the RSA key directly, SPKI keys wrap it
+            let rsa_key = match pem.tag.as_str() {
+                "RSA PUBLIC KEY" => Some(pem.contents.as_slice()),
+                _ => subject_public_key(&pem.contents),
+            };
+            let (n, e) = rsa_key.and_then(rsa_components).ok_or_else(invalid)?;
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: base64_url(n),
+                e: base64_url(e),
+            })
+        }
+    };
+
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            algorithm: Some(key.algorithm),
+            key_id: Some(key.kid.clone()),
+            ..Default::default()
+        },
+        algorithm,
+    })
+}
+
+/// Reads one DER element and returns its tag, content and the remaining bytes
+fn der_read(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&length, data) = data.split_first()?;
+
+    // Long form lengths are prefixed with the number of length bytes
+    let (length, data) = if length & 0x80 == 0 {
+        (length as usize, data)
+    } else {
+        let count = (length & 0x7f) as usize;
+        if count == 0 || count > 4 || data.len() < count {
+            return None;
+        }
+        let (bytes, data) = data.split_at(count);
+        let length = bytes
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, data)
+    };
+
+    if data.len() < length {
+        return None;
+    }
+    let (content, rest) = data.split_at(length);
+    Some((tag, content, rest))
+}
+
+/// Returns the key bytes of a SubjectPublicKeyInfo
+fn subject_public_key(der: &[u8]) -> Option<&[u8]> {
+    let (tag, info, _) = der_read(der)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (_, _, rest) = der_read(info)?;
+    let (tag, bits, _) = der_read(rest)?;
+
+    // First byte of a bit string holds the number of unused bits
+    match bits.split_first() {
+        Some((0, key)) if tag == BIT_STRING => Some(key),
+        _ => None,
+    }
+}
+
+/// Returns modulus and exponent of a PKCS#1 RSA public key
+fn rsa_components(der: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (tag, key, _) = der_read(der)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (n_tag, n, rest) = der_read(key)?;
+    let (e_tag, e, _) = der_read(rest)?;
+    if n_tag != INTEGER || e_tag != INTEGER {
+        return None;
+    }
+
+    Some((strip_leading_zeros(n), strip_leading_zeros(e)))
+}
+
+fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_SPKI: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAtiytDwE9YFJ0wcXYjrbJ
+641poGAfp/ujGsSAysejips5WAcIKzZbo22mgzbz+vz73NNCIVrMD/PR9RivQ+Z1
+DSqUS1qgC1D+e7O7e+pohpI0XSvNLlMY0EKq7VG5Fj987M6IKt5i1t5Zoj7hsSF6
+S6JwjjQGam/8Uinwz7dOZR9BQl0cZJNuj5uVzh3h8tFLKhMEjUxBN5jrsBnbSSgs
+Ee/LpUelgj00JRRRsGitd1UCKV35EW8f08SpYAkYXR6MsdKk6q/JhlV2V/s35Tft
+ycTIAA6/2CfN50Y9XcFNVFvEkW596fi8JHFvRtfHZEfrXlZPjtZ8hXMdDPwscXrL
+FwIDAQAB
+-----END PUBLIC KEY-----";
+    /// Same key as `RSA_SPKI`
+    const RSA_PKCS1: &str = "-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEAtiytDwE9YFJ0wcXYjrbJ641poGAfp/ujGsSAysejips5WAcIKzZb
+o22mgzbz+vz73NNCIVrMD/PR9RivQ+Z1DSqUS1qgC1D+e7O7e+pohpI0XSvNLlMY
+0EKq7VG5Fj987M6IKt5i1t5Zoj7hsSF6S6JwjjQGam/8Uinwz7dOZR9BQl0cZJNu
+j5uVzh3h8tFLKhMEjUxBN5jrsBnbSSgsEe/LpUelgj00JRRRsGitd1UCKV35EW8f
+08SpYAkYXR6MsdKk6q/JhlV2V/s35TftycTIAA6/2CfN50Y9XcFNVFvEkW596fi8
+JHFvRtfHZEfrXlZPjtZ8hXMdDPwscXrLFwIDAQAB
+-----END RSA PUBLIC KEY-----";
+    const RSA_N: &str = "tiytDwE9YFJ0wcXYjrbJ641poGAfp_ujGsSAysejips5WAcIKzZbo22mgzbz-vz73NNCIVrMD_PR9RivQ-Z1DSqUS1qgC1D-e7O7e-pohpI0XSvNLlMY0EKq7VG5Fj987M6IKt5i1t5Zoj7hsSF6S6JwjjQGam_8Uinwz7dOZR9BQl0cZJNuj5uVzh3h8tFLKhMEjUxBN5jrsBnbSSgsEe_LpUelgj00JRRRsGitd1UCKV35EW8f08SpYAkYXR6MsdKk6q_JhlV2V_s35TftycTIAA6_2CfN50Y9XcFNVFvEkW596fi8JHFvRtfHZEfrXlZPjtZ8hXMdDPwscXrLFw";
+    const RSA_E: &str = "AQAB";
+    const P256: &str = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAExF/IJflBcXSwJDHEtgrap+Zardo8
+1vpoEtHlSwNV30f9AVzCK1dsBs7FdTvU/sLObXb/iOWIKHF1BP9MTEa+aA==
+-----END PUBLIC KEY-----";
+    const P256_X: &str = "xF_IJflBcXSwJDHEtgrap-Zardo81vpoEtHlSwNV30c";
+    const P256_Y: &str = "_QFcwitXbAbOxXU71P7Czm12_4jliChxdQT_TExGvmg";
+    const ED25519: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAilGJtYbtDDSzJ18LFXY3y+U88PN5fqjfN/DmysPrgwE=
+-----END PUBLIC KEY-----";
+    const ED25519_X: &str = "ilGJtYbtDDSzJ18LFXY3y-U88PN5fqjfN_DmysPrgwE";
+
+    fn key(algorithm: Algorithm) -> JwtKey {
+        JwtKey {
+            kid: "test-key".to_string(),
+            algorithm,
+            public_key: String::new(),
+            private_key: None,
+        }
+    }
+
+    fn assert_rsa(jwk: Jwk) {
+        match jwk.algorithm {
+            AlgorithmParameters::RSA(parameters) => {
+                assert_eq!(parameters.n, RSA_N);
+                assert_eq!(parameters.e, RSA_E);
+            }
+            other => panic!("Expected RSA parameters, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rsa_spki_key() {
+        let jwk = jwk(&key(Algorithm::RS256), RSA_SPKI.as_bytes()).unwrap();
+
+        assert_eq!(jwk.common.key_id.as_deref(), Some("test-key"));
+        assert_eq!(jwk.common.algorithm, Some(Algorithm::RS256));
+        assert_rsa(jwk);
+    }
+
+    #[test]
+    fn rsa_pkcs1_key() {
+        assert_rsa(jwk(&key(Algorithm::RS256), RSA_PKCS1.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn p256_key() {
+        let jwk = jwk(&key(Algorithm::ES256), P256.as_bytes()).unwrap();
+
+        match jwk.algorithm {
+            AlgorithmParameters::EllipticCurve(parameters) => {
+                assert_eq!(parameters.curve, EllipticCurve::P256);
+                assert_eq!(parameters.x, P256_X);
+                assert_eq!(parameters.y, P256_Y);
+            }
+            other => panic!("Expected elliptic curve parameters, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ed25519_key() {
+        let jwk = jwk(&key(Algorithm::EdDSA), ED25519.as_bytes()).unwrap();
+
+        match jwk.algorithm {
+            AlgorithmParameters::OctetKeyPair(parameters) => {
+                assert_eq!(parameters.curve, EllipticCurve::Ed25519);
+                assert_eq!(parameters.x, ED25519_X);
+            }
+            other => panic!("Expected octet key pair parameters, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn key_of_other_algorithm_is_rejected() {
+        // An Ed25519 key has no uncompressed point or RSA sequence
+        assert!(jwk(&key(Algorithm::ES256), ED25519.as_bytes()).is_err());
+        assert!(jwk(&key(Algorithm::RS256), ED25519.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn truncated_der_is_rejected() {
+        // Sequence announcing 16 bytes with only 2 present
+        assert!(der_read(&[SEQUENCE, 0x10, 0x02, 0x00]).is_none());
+        // Long form length without length bytes
+        assert!(der_read(&[SEQUENCE, 0x80]).is_none());
+        assert!(subject_public_key(&[SEQUENCE, 0x00]).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Utc;
+
+use crate::config::{Mail, MailTransport};
+
+/// A plain text mail
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers mails to users
+pub trait Mailer: Send + Sync {
+    fn send(&self, message: &Message) -> anyhow::Result<()>;
+}
+
+/// Writes mails to the log instead of sending them
+pub struct LogMailer {
+    sender: String,
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, message: &Message) -> anyhow::Result<()> {
+        info!(
+            "Mail from '{}' to '{}' with subject '{}':\n{}",
+            &self.sender, &message.to, &message.subject, &message.body
+        );
+
+        Ok(())
+    }
+}
+
+/// Drops every mail as a file into a directory. Useful for local testing.
+pub struct FileMailer {
+    sender: String,
+    directory: PathBuf,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, message: &Message) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+
+        let file_name = format!(
+            "{}_{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S%f"),
+            message
+                .to
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        );
+        let path = self.directory.join(file_name);
+        debug!("Writing mail to '{}'", path.display());
+
+        fs::write(
+            path,
+            format!(
+                "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+                &self.sender, &message.to, &message.subject, &message.body
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Creates the mailer configured in `Config`
+pub fn from_config(config: &Mail) -> Box<dyn Mailer> {
+    match config.transport {
+        MailTransport::Log => Box::new(LogMailer {
+            sender: config.sender.clone(),
+        }),
+        MailTransport::File => Box::new(FileMailer {
+            sender: config.sender.clone(),
+            directory: PathBuf::from(&config.directory),
+        }),
+    }
+}
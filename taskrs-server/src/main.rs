@@ -13,16 +13,32 @@ use dotenv::dotenv;
 use taskrs_db::{DbConnection, DbPool};
 
 mod api;
+mod authenticator;
 mod config;
+mod jwt;
+mod mailer;
+mod maintenance;
 mod middleware;
 mod models;
+mod oidc;
+mod password_policy;
 pub mod permissions;
+mod totp;
 pub mod utils;
 
 lazy_static! {
     static ref CONFIG: crate::config::Config =
         crate::config::Config::new().expect("Error reading config");
+    static ref JWT_KEYS: crate::jwt::JwtKeys =
+        crate::jwt::JwtKeys::from_config(&CONFIG).expect("Error loading JWT keys");
+    static ref AUTHENTICATORS: Vec<Box<dyn crate::authenticator::Authenticator>> =
+        crate::authenticator::from_config(&CONFIG);
+    static ref MAILER: Box<dyn crate::mailer::Mailer> = crate::mailer::from_config(&CONFIG.mail);
+    static ref PASSWORD_POLICY: crate::password_policy::PasswordPolicy =
+        crate::password_policy::PasswordPolicy::from_config(&CONFIG.password_policy)
+            .expect("Error loading password policy");
     static ref PERMISSION_CACHE: RwLock<HashMap<i32, Vec<String>>> = RwLock::new(HashMap::new());
+    static ref TOKEN_VERSION_CACHE: RwLock<HashMap<i32, i32>> = RwLock::new(HashMap::new());
 }
 
 #[actix_web::main]
@@ -30,6 +46,8 @@ async fn main() -> std::io::Result<()> {
     // Logger, .env, Database, Migrations
     log4rs::init_file("config/log.yml", Default::default()).unwrap();
     dotenv().ok();
+    lazy_static::initialize(&JWT_KEYS);
+    lazy_static::initialize(&PASSWORD_POLICY);
     let pool = taskrs_db::connect_database(
         &CONFIG.database.host,
         &CONFIG.database.port,
@@ -41,17 +59,26 @@ async fn main() -> std::io::Result<()> {
     let conn = pool.get().expect("Couldn't get db connection from pool");
     setup_database(&conn);
 
+    // Only run maintenance once with `taskrs-server cleanup`
+    if std::env::args().nth(1).as_deref() == Some("cleanup") {
+        maintenance::run(&conn).expect("Error running maintenance");
+        return Ok(());
+    }
+
     start(pool).await
 }
 
 async fn start(pool: DbPool) -> std::io::Result<()> {
+    maintenance::start(pool.clone());
+
     HttpServer::new(move || {
         let mut app = App::new()
             .wrap(
                 Cors::default()
                     .allow_any_origin()
                     .allow_any_method()
-                    .allow_any_header(),
+                    .allow_any_header()
+                    .expose_headers(vec![actix_web::http::header::ETAG]),
             )
             .wrap(actix_web::middleware::Logger::new(
                 "%r responded %s in %D ms",
@@ -66,6 +93,9 @@ async fn start(pool: DbPool) -> std::io::Result<()> {
         api_scope = api::permissions::register(api_scope);
         api_scope = api::categories::register(api_scope);
         api_scope = api::projects::register(api_scope);
+        api_scope = api::roles::register(api_scope);
+        api_scope = api::tasks::register(api_scope);
+        api_scope = api::tokens::register(api_scope);
 
         app = app.service(api_scope);
         app
@@ -81,6 +111,9 @@ async fn start(pool: DbPool) -> std::io::Result<()> {
 fn setup_database(conn: &DbConnection) {
     taskrs_db::run_migrations(conn).expect("Error running migrations");
 
+    taskrs_db::update_permissions(permissions::all_permissions(), conn)
+        .expect("Error updating permissions");
+
     taskrs_db::seed_root_permissions(
         &CONFIG.root_user_email,
         &CONFIG.root_user_password,
@@ -89,6 +122,5 @@ fn setup_database(conn: &DbConnection) {
     )
     .expect("Error seeding root user/permissions");
 
-    taskrs_db::update_permissions(permissions::all_permissions(), conn)
-        .expect("Error updating permissions");
+    taskrs_db::seed_roles(&CONFIG.roles, conn).expect("Error seeding roles");
 }
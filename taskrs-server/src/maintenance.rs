@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use actix_web::{rt, web};
+
+use taskrs_db::{DbConnection, DbPool};
+
+use crate::CONFIG;
+
+/// Starts the periodic maintenance task.
+/// Does nothing if `maintenance_interval` is 0.
+pub fn start(pool: DbPool) {
+    if CONFIG.maintenance_interval == 0 {
+        info!("Maintenance task is disabled");
+        return;
+    }
+
+    rt::spawn(async move {
+        let mut interval =
+            rt::time::interval(Duration::from_secs(CONFIG.maintenance_interval as u64));
+
+        loop {
+            interval.tick().await;
+
+            let pool = pool.clone();
+            let result = web::block(move || {
+                let conn = pool.get()?;
+                run(&conn)
+            })
+            .await;
+
+            if let Err(e) = result {
+                error!("Maintenance failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Runs all maintenance jobs once
+pub fn run(conn: &DbConnection) -> anyhow::Result<()> {
+    let (refresh_tokens, password_reset_tokens, invitations) =
+        taskrs_db::purge_expired_tokens(conn)?;
+    info!(
+        "Deleted {} expired refresh tokens, {} expired password reset tokens and {} expired invitations",
+        refresh_tokens, password_reset_tokens, invitations
+    );
+
+    let login_failures =
+        taskrs_db::purge_login_failures(CONFIG.login_protection.failure_window as i64, conn)?;
+    info!("Deleted {} outdated login failures", login_failures);
+
+    let (users, projects, categories) =
+        taskrs_db::purge_deleted(CONFIG.deleted_retention_time as i64, conn)?;
+    info!(
+        "Purged {} deleted users, {} deleted projects and {} deleted categories",
+        users, projects, categories
+    );
+
+    Ok(())
+}
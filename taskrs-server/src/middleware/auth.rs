@@ -4,14 +4,12 @@ use std::task::{Context, Poll};
 use actix_service::{Service, Transform};
 use actix_web::http::{HeaderName, HeaderValue, Method};
 use actix_web::web::Data;
-use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, HttpResponse};
+use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, HttpMessage, HttpResponse};
 use futures::future::{ok, Ready};
 use futures::Future;
 
 use taskrs_db::DbPool;
 
-use crate::CONFIG;
-
 pub struct Authentication;
 
 impl<S, B> Transform<S> for Authentication
@@ -66,7 +64,7 @@ where
             authenticate_pass = true;
         } else {
             // Get Database Pool
-            if let Some(_pool) = req.app_data::<Data<DbPool>>() {
+            if let Some(pool) = req.app_data::<Data<DbPool>>() {
                 // Get Authorization Header
                 match req.headers().get("authorization") {
                     None => {
@@ -78,14 +76,20 @@ where
                             if auth_str.starts_with("bearer") || auth_str.starts_with("Bearer") {
                                 // Trim Bearer word
                                 let token: &str = auth_str[6..auth_str.len()].trim();
-                                // Decode token
-                                match crate::utils::decode_token(token, &CONFIG.access_token_secret)
+                                // Decode token or look up API token
+                                match crate::utils::get_db_connection(pool.clone().into_inner())
+                                    .and_then(|conn| crate::utils::authenticate(token, &conn))
                                 {
-                                    Ok(_data) => {
+                                    Ok(Some(user)) => {
+                                        // Handlers take the user from the extensions
+                                        req.extensions_mut().insert(user);
                                         authenticate_pass = true;
                                     }
+                                    Ok(None) => {
+                                        debug!("Token is invalid");
+                                    }
                                     Err(err) => {
-                                        debug!("Error while decoding token: {}", err);
+                                        return Box::pin(async move { Err(err) });
                                     }
                                 }
                             } else {
@@ -8,6 +8,12 @@ pub struct DeleteEntityParams {
     pub cascade: Option<bool>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreEntityParams {
+    pub id: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DeleteEntityResult<T> {
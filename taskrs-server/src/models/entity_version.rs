@@ -0,0 +1,220 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use taskrs_db::models::category::Category;
+use taskrs_db::models::project::Project;
+use taskrs_db::models::task::Task;
+use taskrs_db::models::user::User;
+use taskrs_db::DbConnection;
+
+use crate::models::user_token::TokenUser;
+
+/// Format of the version in ETags, Postgres stores microseconds
+const VERSION_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f";
+
+/// Entities that use `updated_at` as version for optimistic concurrency.
+/// The ETag is the quoted `updatedAt` of the entity, so versions can also be taken from lists.
+pub trait Versioned {
+    fn version(&self) -> Option<NaiveDateTime>;
+
+    /// Locks the row until the end of the transaction and returns its version.
+    /// Returns `None` if the row does not exist.
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>>;
+
+    fn etag(&self) -> Option<String> {
+        self.version()
+            .map(|version| format!("\"{}\"", version.format(VERSION_FORMAT)))
+    }
+}
+
+impl Versioned for User {
+    fn version(&self) -> Option<NaiveDateTime> {
+        self.updated_at
+    }
+
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>> {
+        use taskrs_db::schema::users;
+
+        users::table
+            .find(id)
+            .select(users::updated_at)
+            .for_update()
+            .first(conn)
+            .optional()
+    }
+}
+
+impl Versioned for TokenUser {
+    fn version(&self) -> Option<NaiveDateTime> {
+        self.updated_at
+    }
+
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>> {
+        User::lock_version(id, conn)
+    }
+}
+
+impl Versioned for Category {
+    fn version(&self) -> Option<NaiveDateTime> {
+        self.updated_at
+    }
+
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>> {
+        use taskrs_db::schema::categories;
+
+        categories::table
+            .find(id)
+            .select(categories::updated_at)
+            .for_update()
+            .first(conn)
+            .optional()
+    }
+}
+
+impl Versioned for Project {
+    fn version(&self) -> Option<NaiveDateTime> {
+        self.updated_at
+    }
+
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>> {
+        use taskrs_db::schema::projects;
+
+        projects::table
+            .find(id)
+            .select(projects::updated_at)
+            .for_update()
+            .first(conn)
+            .optional()
+    }
+}
+
+impl Versioned for Task {
+    fn version(&self) -> Option<NaiveDateTime> {
+        self.updated_at
+    }
+
+    fn lock_version(
+        id: i32,
+        conn: &DbConnection,
+    ) -> diesel::QueryResult<Option<Option<NaiveDateTime>>> {
+        use taskrs_db::schema::tasks;
+
+        tasks::table
+            .find(id)
+            .select(tasks::updated_at)
+            .for_update()
+            .first(conn)
+            .optional()
+    }
+}
+
+/// Versions of the `If-Match` header, `None` if the header is missing
+#[derive(Debug, Clone, Default)]
+pub struct IfMatch(Option<Vec<String>>);
+
+impl IfMatch {
+    /// Weak tags never match, `If-Match` uses the strong comparison
+    pub fn matches(&self, version: Option<NaiveDateTime>) -> bool {
+        let tags = match &self.0 {
+            None => return true,
+            Some(tags) => tags,
+        };
+
+        tags.iter().any(|tag| {
+            tag == "*"
+                || (version.is_some()
+                    && tag.len() >= 2
+                    && tag.starts_with('"')
+                    && tag.ends_with('"')
+                    && NaiveDateTime::parse_from_str(
+                        &tag[1..tag.len() - 1],
+                        "%Y-%m-%dT%H:%M:%S%.f",
+                    )
+                    .ok()
+                        == version)
+        })
+    }
+}
+
+impl FromRequest for IfMatch {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let tags = req.headers().get(header::IF_MATCH).map(|value| {
+            value
+                .to_str()
+                .unwrap_or_default()
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        });
+
+        futures::future::ok(IfMatch(tags))
+    }
+}
+
+pub enum IfMatchResult<T> {
+    Ok(T),
+    PreconditionFailed,
+}
+
+/// Runs `action` if the entity still has the version the client read.
+/// Rows that do not exist are left to `action`.
+pub fn if_match<V, T, E, F>(
+    id: i32,
+    if_match: &IfMatch,
+    conn: &DbConnection,
+    action: F,
+) -> Result<IfMatchResult<T>, E>
+where
+    V: Versioned,
+    E: From<diesel::result::Error>,
+    F: FnOnce() -> Result<T, E>,
+{
+    if if_match.0.is_none() {
+        return action().map(IfMatchResult::Ok);
+    }
+
+    conn.transaction(|| {
+        if let Some(version) = V::lock_version(id, conn)? {
+            if !if_match.matches(version) {
+                debug!("Entity {} changed since it was read", id);
+                return Ok(IfMatchResult::PreconditionFailed);
+            }
+        }
+
+        action().map(IfMatchResult::Ok)
+    })
+}
+
+/// Responds with the entity and its version as ETag
+pub fn versioned_response<V: Versioned + Serialize>(entity: V) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(etag) = entity.etag() {
+        response.set_header(header::ETAG, etag);
+    }
+
+    response.json(entity)
+}
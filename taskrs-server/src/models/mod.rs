@@ -1,4 +1,5 @@
 pub mod create_entity_result;
 pub mod delete_entity;
+pub mod entity_version;
 pub mod request_filter;
 pub mod user_token;
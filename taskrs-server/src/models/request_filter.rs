@@ -8,6 +8,8 @@ pub struct RequestFilter<T> {
     pub order: Option<Order>,
     pub page: Option<i64>,
     pub limit: Option<i64>,
+    /// Also list entities in the trash
+    pub include_deleted: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
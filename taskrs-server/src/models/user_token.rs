@@ -1,12 +1,21 @@
+use std::ops::Deref;
+
 use actix_web::dev::Payload;
-use actix_web::FromRequest;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
 use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use taskrs_db::models::user::User;
+use taskrs_db::token::generate_token;
+use taskrs_db::DbPool;
 
+use crate::jwt::InternalClaims;
 use crate::CONFIG;
 
+/// Prefix of personal API tokens, used to tell them apart from JWTs
+pub const API_TOKEN_PREFIX: &str = "taskrs_";
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenUser {
@@ -17,6 +26,11 @@ pub struct TokenUser {
     pub activated: bool,
     pub updated_at: Option<NaiveDateTime>,
     pub created_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub token_version: i32,
+    /// Permissions the user is limited to when authenticated with an API token
+    #[serde(skip)]
+    pub api_token_permissions: Option<Vec<String>>,
 }
 
 impl From<User> for TokenUser {
@@ -29,6 +43,7 @@ impl From<User> for TokenUser {
             activated,
             updated_at,
             created_at,
+            token_version,
             ..
         }: User,
     ) -> Self {
@@ -40,16 +55,19 @@ impl From<User> for TokenUser {
             activated,
             updated_at,
             created_at,
+            token_version,
+            api_token_permissions: None,
         }
     }
 }
 
-impl FromRequest for TokenUser {
-    type Error = actix_web::Error;
-    type Future = futures::future::Ready<Result<Self, Self::Error>>;
-    type Config = ();
+impl TokenUser {
+    /// Returns the user authenticated by the middleware or authenticates the bearer token
+    fn from_http_request(req: &HttpRequest) -> Result<Self, actix_web::Error> {
+        if let Some(user) = req.extensions().get::<TokenUser>() {
+            return Ok(user.clone());
+        }
 
-    fn from_request(req: &actix_web::HttpRequest, _payload: &mut Payload) -> Self::Future {
         let mut user: Option<TokenUser> = None;
 
         // Get Authorization Header
@@ -59,24 +77,69 @@ impl FromRequest for TokenUser {
                 if auth_str.starts_with("bearer") || auth_str.starts_with("Bearer") {
                     // Trim Bearer word
                     let token: &str = auth_str[6..auth_str.len()].trim();
-                    // Decode token
-                    if let Ok(token_data) =
-                        crate::utils::decode_token(token, &CONFIG.access_token_secret)
-                    {
-                        user = Some(token_data.claims.user);
-                    }
+                    // Decode token or look up API token
+                    user = match req.app_data::<Data<DbPool>>() {
+                        Some(pool) => crate::utils::get_db_connection(pool.clone().into_inner())
+                            .and_then(|conn| crate::utils::authenticate(token, &conn))?,
+                        // No Pool found
+                        None => unreachable!(),
+                    };
                 }
             }
         }
 
-        if let Some(user) = user {
-            futures::future::ok(user)
-        } else {
-            futures::future::err(actix_web::HttpResponse::Unauthorized().finish().into())
+        match user {
+            Some(user) => {
+                req.extensions_mut().insert(user.clone());
+                Ok(user)
+            }
+            None => Err(HttpResponse::Unauthorized().finish().into()),
         }
     }
 }
 
+impl FromRequest for TokenUser {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        futures::future::ready(TokenUser::from_http_request(req))
+    }
+}
+
+/// User authenticated with an access token.
+/// API tokens are rejected, so they can't manage the account they belong to.
+#[derive(Debug, Clone)]
+pub struct JwtUser(pub TokenUser);
+
+impl Deref for JwtUser {
+    type Target = TokenUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for JwtUser {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = TokenUser::from_http_request(req).and_then(|user| {
+            if user.api_token_permissions.is_some() {
+                debug!("API token used for user {} is not allowed here", user.id);
+                Err(HttpResponse::Forbidden().finish().into())
+            } else {
+                Ok(JwtUser(user))
+            }
+        });
+
+        futures::future::ready(result)
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserToken {
@@ -112,6 +175,9 @@ impl From<&TokenUser> for UserToken {
 pub struct UserRefreshToken {
     pub iat: i64,
     pub exp: i64,
+    /// Random id, so tokens issued in the same second differ
+    #[serde(default)]
+    pub jti: String,
     pub user_email: String,
 }
 
@@ -121,6 +187,7 @@ impl From<TokenUser> for UserRefreshToken {
         UserRefreshToken {
             iat: now,
             exp: now + (CONFIG.refresh_token_expiration_time as i64),
+            jti: generate_token(),
             user_email: user.email,
         }
     }
@@ -132,6 +199,7 @@ impl From<&TokenUser> for UserRefreshToken {
         UserRefreshToken {
             iat: now,
             exp: now + (CONFIG.refresh_token_expiration_time as i64),
+            jti: generate_token(),
             user_email: user.email.clone(),
         }
     }
@@ -143,6 +211,7 @@ impl From<User> for UserRefreshToken {
         UserRefreshToken {
             iat: now,
             exp: now + (CONFIG.refresh_token_expiration_time as i64),
+            jti: generate_token(),
             user_email: user.email,
         }
     }
@@ -154,7 +223,86 @@ impl From<&User> for UserRefreshToken {
         UserRefreshToken {
             iat: now,
             exp: now + (CONFIG.refresh_token_expiration_time as i64),
+            jti: generate_token(),
             user_email: user.email.clone(),
         }
     }
 }
+
+/// Proof that the password was correct, exchanged for tokens together with a TOTP code
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpChallenge {
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub user_id: i32,
+}
+
+impl TotpChallenge {
+    pub fn new(user_id: i32) -> Self {
+        let now = Utc::now().timestamp();
+        TotpChallenge {
+            aud: Self::AUDIENCE.to_string(),
+            iat: now,
+            exp: now + (CONFIG.totp_challenge_expiration_time as i64),
+            user_id,
+        }
+    }
+}
+
+impl InternalClaims for TotpChallenge {
+    const AUDIENCE: &'static str = "totp_challenge";
+}
+
+/// Token sent to invited users for setting their password
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InvitationToken {
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub user_id: i32,
+    pub jti: String,
+}
+
+impl InvitationToken {
+    pub fn new(user_id: i32) -> Self {
+        let now = Utc::now().timestamp();
+        InvitationToken {
+            aud: Self::AUDIENCE.to_string(),
+            iat: now,
+            exp: now + (CONFIG.invitation_expiration_time as i64),
+            user_id,
+            jti: generate_token(),
+        }
+    }
+}
+
+impl InternalClaims for InvitationToken {
+    const AUDIENCE: &'static str = "invitation";
+}
+
+/// Signed `state` of an OpenID Connect login, holds the nonce expected in the ID token
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OidcState {
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub nonce: String,
+}
+
+impl OidcState {
+    pub fn new(nonce: String) -> Self {
+        let now = Utc::now().timestamp();
+        OidcState {
+            aud: Self::AUDIENCE.to_string(),
+            iat: now,
+            exp: now + (CONFIG.oidc.state_expiration_time as i64),
+            nonce,
+        }
+    }
+}
+
+impl InternalClaims for OidcState {
+    const AUDIENCE: &'static str = "oidc_state";
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::PasswordPolicy as PasswordPolicyConfig;
+
+/// Denied in addition to the configured deny-list
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "password",
+    "password1",
+    "password123",
+    "passw0rd",
+    "qwerty",
+    "qwerty123",
+    "qwertz",
+    "abc123",
+    "111111",
+    "000000",
+    "iloveyou",
+    "letmein",
+    "welcome",
+    "admin",
+    "root",
+    "changeme",
+    "secret",
+    "taskrs",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PasswordRule {
+    MinLength,
+    Lowercase,
+    Uppercase,
+    Digit,
+    Special,
+    DenyList,
+    NotEmail,
+}
+
+/// A rule the password does not satisfy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordViolation {
+    pub rule: PasswordRule,
+    pub message: String,
+}
+
+impl PasswordViolation {
+    fn new(rule: PasswordRule, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            message: message.into(),
+        }
+    }
+}
+
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+    deny_list: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &PasswordPolicyConfig) -> anyhow::Result<Self> {
+        let mut deny_list: HashSet<String> = COMMON_PASSWORDS
+            .iter()
+            .map(|password| password.to_string())
+            .collect();
+
+        // One password per line
+        if !config.deny_list.is_empty() {
+            deny_list.extend(
+                std::fs::read_to_string(&config.deny_list)?
+                    .lines()
+                    .map(|line| line.trim().to_lowercase())
+                    .filter(|line| !line.is_empty()),
+            );
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            deny_list,
+        })
+    }
+
+    /// Returns every rule the password violates
+    pub fn validate(&self, password: &str, email: &str) -> Vec<PasswordViolation> {
+        let mut violations = vec![];
+
+        if password.chars().count() < self.config.min_length as usize {
+            violations.push(PasswordViolation::new(
+                PasswordRule::MinLength,
+                format!(
+                    "Password must be at least {} characters long",
+                    self.config.min_length
+                ),
+            ));
+        }
+        if self.config.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::Lowercase,
+                "Password must contain a lowercase letter",
+            ));
+        }
+        if self.config.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::Uppercase,
+                "Password must contain an uppercase letter",
+            ));
+        }
+        if self.config.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::Digit,
+                "Password must contain a digit",
+            ));
+        }
+        if self.config.require_special && password.chars().all(char::is_alphanumeric) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::Special,
+                "Password must contain a special character",
+            ));
+        }
+        if self.config.deny_common && self.deny_list.contains(&password.to_lowercase()) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::DenyList,
+                "Password is too common",
+            ));
+        }
+        if self.config.deny_email && password.eq_ignore_ascii_case(email) {
+            violations.push(PasswordViolation::new(
+                PasswordRule::NotEmail,
+                "Password must not be the email",
+            ));
+        }
+
+        violations
+    }
+}
@@ -9,4 +9,14 @@ lazy_static! {
         updated_at: None,
         created_at: None,
     };
+    pub static ref AUTH_UNLOCK_LOGIN: Permission = Permission {
+        id: 0,
+        name: "auth_unlock_login".to_string(),
+        group: "auth".to_string(),
+        description: Some(
+            "Allows a user to unlock emails and IPs locked after failed logins".to_string()
+        ),
+        updated_at: None,
+        created_at: None,
+    };
 }
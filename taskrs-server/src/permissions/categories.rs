@@ -25,6 +25,14 @@ lazy_static! {
         updated_at: None,
         created_at: None,
     };
+    pub static ref CATEGORY_RESTORE: Permission = Permission {
+        id: 0,
+        name: "category_restore".to_string(),
+        group: "category".to_string(),
+        description: Some("Allows a user to restore deleted categories".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
     pub static ref CATEGORY_UPDATE: Permission = Permission {
         id: 0,
         name: "category_update".to_string(),
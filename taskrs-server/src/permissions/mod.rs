@@ -4,21 +4,27 @@ pub use auth::*;
 pub use categories::*;
 pub use permissions::*;
 pub use projects::*;
+pub use roles::*;
 use taskrs_db::models::permission::Permission;
+pub use tasks::*;
 pub use users::*;
 
 mod auth;
 mod categories;
 mod permissions;
 mod projects;
+mod roles;
+mod tasks;
 mod users;
 
 pub fn all_permissions() -> Vec<&'static Permission> {
     vec![
         &auth::AUTH_REVOKE_REFRESH_TOKEN,
+        &auth::AUTH_UNLOCK_LOGIN,
         &categories::CATEGORY_GET_ALL,
         &categories::CATEGORY_CREATE,
         &categories::CATEGORY_DELETE,
+        &categories::CATEGORY_RESTORE,
         &categories::CATEGORY_UPDATE,
         &permissions::PERMISSION_GET_ALL,
         &permissions::PERMISSION_SET,
@@ -27,10 +33,26 @@ pub fn all_permissions() -> Vec<&'static Permission> {
         &projects::PROJECT_GET_ALL,
         &projects::PROJECT_CREATE,
         &projects::PROJECT_DELETE,
+        &projects::PROJECT_RESTORE,
         &projects::PROJECT_UPDATE,
+        &projects::PROJECT_MEMBER_GET_ALL,
+        &projects::PROJECT_MEMBER_ADD,
+        &projects::PROJECT_MEMBER_REMOVE,
+        &projects::PROJECT_MEMBER_UPDATE,
+        &roles::ROLE_GET_ALL,
+        &roles::ROLE_CREATE,
+        &roles::ROLE_DELETE,
+        &roles::ROLE_UPDATE,
+        &roles::ROLE_GRANT,
+        &roles::ROLE_REVOKE,
+        &tasks::TASK_GET_ALL,
+        &tasks::TASK_CREATE,
+        &tasks::TASK_DELETE,
+        &tasks::TASK_UPDATE,
         &users::USER_GET_ALL,
         &users::USER_CREATE,
         &users::USER_DELETE,
+        &users::USER_RESTORE,
         &users::USER_UPDATE,
     ]
 }
@@ -25,6 +25,14 @@ lazy_static! {
         updated_at: None,
         created_at: None,
     };
+    pub static ref PROJECT_RESTORE: Permission = Permission {
+        id: 0,
+        name: "project_restore".to_string(),
+        group: "project".to_string(),
+        description: Some("Allows a user to restore deleted projects".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
     pub static ref PROJECT_UPDATE: Permission = Permission {
         id: 0,
         name: "project_update".to_string(),
@@ -33,4 +41,36 @@ lazy_static! {
         updated_at: None,
         created_at: None,
     };
+    pub static ref PROJECT_MEMBER_GET_ALL: Permission = Permission {
+        id: 0,
+        name: "project_member_get_all".to_string(),
+        group: "project".to_string(),
+        description: Some("Allows a user to get all members of projects".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref PROJECT_MEMBER_ADD: Permission = Permission {
+        id: 0,
+        name: "project_member_add".to_string(),
+        group: "project".to_string(),
+        description: Some("Allows a user to add members to projects".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref PROJECT_MEMBER_REMOVE: Permission = Permission {
+        id: 0,
+        name: "project_member_remove".to_string(),
+        group: "project".to_string(),
+        description: Some("Allows a user to remove members from projects".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref PROJECT_MEMBER_UPDATE: Permission = Permission {
+        id: 0,
+        name: "project_member_update".to_string(),
+        group: "project".to_string(),
+        description: Some("Allows a user to promote or demote members of projects".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
 }
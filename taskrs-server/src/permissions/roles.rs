@@ -0,0 +1,52 @@
+use taskrs_db::models::permission::Permission;
+
+lazy_static! {
+    pub static ref ROLE_GET_ALL: Permission = Permission {
+        id: 0,
+        name: "role_get_all".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to get all roles".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref ROLE_CREATE: Permission = Permission {
+        id: 0,
+        name: "role_create".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to create new roles".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref ROLE_DELETE: Permission = Permission {
+        id: 0,
+        name: "role_delete".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to delete roles".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref ROLE_UPDATE: Permission = Permission {
+        id: 0,
+        name: "role_update".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to update roles and their permissions".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref ROLE_GRANT: Permission = Permission {
+        id: 0,
+        name: "role_grant".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to grant roles to a user".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref ROLE_REVOKE: Permission = Permission {
+        id: 0,
+        name: "role_revoke".to_string(),
+        group: "role".to_string(),
+        description: Some("Allows a user to revoke roles of a user".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+}
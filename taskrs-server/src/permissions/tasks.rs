@@ -0,0 +1,36 @@
+use taskrs_db::models::permission::Permission;
+
+lazy_static! {
+    pub static ref TASK_GET_ALL: Permission = Permission {
+        id: 0,
+        name: "task_get_all".to_string(),
+        group: "task".to_string(),
+        description: Some("Allows a user to get all tasks".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref TASK_CREATE: Permission = Permission {
+        id: 0,
+        name: "task_create".to_string(),
+        group: "task".to_string(),
+        description: Some("Allows a user to create new tasks".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref TASK_DELETE: Permission = Permission {
+        id: 0,
+        name: "task_delete".to_string(),
+        group: "task".to_string(),
+        description: Some("Allows a user to delete tasks".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+    pub static ref TASK_UPDATE: Permission = Permission {
+        id: 0,
+        name: "task_update".to_string(),
+        group: "task".to_string(),
+        description: Some("Allows a user to update tasks".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
+}
@@ -25,6 +25,14 @@ lazy_static! {
         updated_at: None,
         created_at: None,
     };
+    pub static ref USER_RESTORE: Permission = Permission {
+        id: 0,
+        name: "user_restore".to_string(),
+        group: "user".to_string(),
+        description: Some("Allows a user to restore deleted users".to_string()),
+        updated_at: None,
+        created_at: None,
+    };
     pub static ref USER_UPDATE: Permission = Permission {
         id: 0,
         name: "user_update".to_string(),
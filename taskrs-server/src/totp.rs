@@ -0,0 +1,84 @@
+use base32::Alphabet;
+use hmac::{Hmac, Mac, NewMac};
+use sha1::Sha1;
+
+// RFC 6238 defaults expected by authenticator apps (HMAC-SHA1, 6 digits, 30 seconds)
+const DIGITS: u32 = 6;
+const PERIOD: i64 = 30;
+/// Accepted steps before and after the current one to allow for clock drift
+const SKEW: i64 = 1;
+const RECOVERY_CODES: usize = 10;
+
+const ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+
+/// Generates a random base32 encoded secret
+pub fn generate_secret() -> String {
+    base32::encode(ALPHABET, &rand::random::<[u8; 20]>())
+}
+
+/// Generates a set of single-use recovery codes
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            rand::random::<[u8; 8]>()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the `otpauth://` URI used for QR codes
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode_uri_component(issuer),
+        encode_uri_component(account),
+        secret,
+        encode_uri_component(issuer),
+        DIGITS,
+        PERIOD
+    )
+}
+
+/// Checks the code against the secret at the given unix time.
+/// Returns the matched time step, which must be greater than `last_used_step` so codes can't be
+/// used twice.
+pub fn verify(secret: &str, code: &str, now: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let key = base32::decode(ALPHABET, secret)?;
+    let code = code.trim().parse::<u32>().ok()?;
+    let current_step = now / PERIOD;
+
+    (current_step - SKEW..=current_step + SKEW)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| generate_code(&key, *step) == code)
+}
+
+fn generate_code(key: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    binary % 10u32.pow(DIGITS)
+}
+
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -1,25 +1,25 @@
+use std::net::IpAddr;
 use std::sync::Arc;
 
+use actix_web::HttpRequest;
+use chrono::Utc;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
-use jsonwebtoken::{DecodingKey, TokenData, Validation};
+use jsonwebtoken::TokenData;
 
+use taskrs_db::models::api_token::ApiToken;
 use taskrs_db::models::permission::Permission;
+use taskrs_db::models::user::User;
+use taskrs_db::token::hash_token;
 use taskrs_db::{DbConnection, DbPool};
 
-use crate::models::user_token::{TokenUser, UserToken};
+use crate::models::user_token::{TokenUser, UserToken, API_TOKEN_PREFIX};
+use crate::{CONFIG, JWT_KEYS, PASSWORD_POLICY};
 
 /// Decodes and validates the JWT
 /// Returns Error if token is invalid
-pub fn decode_token(
-    token: &str,
-    secret: &str,
-) -> jsonwebtoken::errors::Result<TokenData<UserToken>> {
-    jsonwebtoken::decode::<UserToken>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )
+pub fn decode_token(token: &str) -> jsonwebtoken::errors::Result<TokenData<UserToken>> {
+    JWT_KEYS.decode::<UserToken>(token)
 }
 
 /// Get database connection from pool.
@@ -51,9 +51,16 @@ pub fn has_one_permission(
             .map(|x| &x.name)
             .collect::<Vec<&String>>()
     );
+    // API tokens are limited to their permissions
+    let allowed_permissions: Vec<&Permission> = needed_permissions
+        .iter()
+        .copied()
+        .filter(|needed_permission| api_token_allows(user, needed_permission))
+        .collect();
+
     // Check cache
     if let Some(cache) = crate::PERMISSION_CACHE.read().unwrap().get(&user.id) {
-        for needed_permission in &needed_permissions {
+        for needed_permission in &allowed_permissions {
             if cache.contains(&needed_permission.name) {
                 // User has one permission
                 debug!("User permission was found in cache");
@@ -69,7 +76,7 @@ pub fn has_one_permission(
     })?;
 
     // Check DB permissions
-    for needed_permission in &needed_permissions {
+    for needed_permission in &allowed_permissions {
         if db_permissions.contains(&needed_permission.name) {
             // User has one permission
             debug!("User permission was found in database");
@@ -106,9 +113,14 @@ pub fn has_all_permissions(
             .map(|x| &x.name)
             .collect::<Vec<&String>>()
     );
+    // API tokens are limited to their permissions
+    let allowed_by_token = needed_permissions
+        .iter()
+        .all(|needed_permission| api_token_allows(user, needed_permission));
+
     // Check cache
     if let Some(cache) = crate::PERMISSION_CACHE.read().unwrap().get(&user.id) {
-        let mut has_all_permissions = true;
+        let mut has_all_permissions = allowed_by_token;
         for needed_permission in &needed_permissions {
             if !cache.contains(&needed_permission.name) {
                 // User doesn't have permission
@@ -131,7 +143,7 @@ pub fn has_all_permissions(
     })?;
 
     // Check DB permissions
-    let mut has_all_permissions = true;
+    let mut has_all_permissions = allowed_by_token;
     for needed_permission in &needed_permissions {
         if !db_permissions.contains(&needed_permission.name) {
             // User doesn't have permission
@@ -167,16 +179,54 @@ pub fn has_permission(
     needed_permission: &Permission,
     conn: &PgConnection,
 ) -> Result<(), actix_web::Error> {
+    if user_has_permission(user, needed_permission, conn)? {
+        return Ok(());
+    }
+
+    // User doesn't have permission
+    debug!("User does not have permission");
+    Err(actix_web::HttpResponse::Forbidden()
+        .body(format!("Needs permission: {:?}", &needed_permission.name))
+        .into())
+}
+
+/// Checks the password against the password policy
+/// Returns BadRequest with the violated rules if it does not comply
+pub fn check_password_policy(password: &str, email: &str) -> Result<(), actix_web::Error> {
+    let violations = PASSWORD_POLICY.validate(password, email);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    debug!("Password violates the password policy");
+    Err(actix_web::HttpResponse::BadRequest()
+        .json(violations)
+        .into())
+}
+
+/// Checks if user has permission without failing if it is missing
+/// Returns InternalServerError on DB error
+pub fn user_has_permission(
+    user: &TokenUser,
+    needed_permission: &Permission,
+    conn: &PgConnection,
+) -> Result<bool, actix_web::Error> {
     debug!(
         "Check if user {} has permission: {}",
         user.id, needed_permission.name
     );
+    // API tokens are limited to their permissions
+    if !api_token_allows(user, needed_permission) {
+        debug!("API token does not have permission");
+        return Ok(false);
+    }
+
     // Check cache
     if let Some(cache) = crate::PERMISSION_CACHE.read().unwrap().get(&user.id) {
         if cache.contains(&needed_permission.name) {
             // User has permission
             debug!("User permission was found in cache");
-            return Ok(());
+            return Ok(true);
         }
     }
 
@@ -190,27 +240,129 @@ pub fn has_permission(
     if db_permissions.contains(&needed_permission.name) {
         // User has permission
         debug!("User permission was found in database");
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Checks if user has the global permission or at least the needed role inside the project
+/// Returns InternalServerError on DB error
+/// Returns Forbidden if neither is matched
+pub fn has_project_permission(
+    user: &TokenUser,
+    needed_permission: &Permission,
+    project_id: i32,
+    needed_role: ProjectRole,
+    conn: &PgConnection,
+) -> Result<(), actix_web::Error> {
+    if user_has_permission(user, needed_permission, conn)? {
         return Ok(());
     }
 
-    // User doesn't have permission
-    debug!("User does not have permission");
-    Err(actix_web::HttpResponse::Forbidden()
-        .body(format!("Needs permission: {:?}", &needed_permission.name))
-        .into())
+    debug!(
+        "Check if user {} has role {:?} in project {}",
+        user.id, needed_role, project_id
+    );
+    // Project roles don't apply to API tokens
+    let role = match user.api_token_permissions {
+        Some(_) => None,
+        None => project_role(user.id, project_id, conn).map_err(|err| {
+            error!("{}", err);
+            actix_web::HttpResponse::InternalServerError().finish()
+        })?,
+    };
+
+    match role {
+        Some(role) if role >= needed_role => {
+            debug!("User has role {:?} in project", role);
+            Ok(())
+        }
+        _ => {
+            // User doesn't have permission or role
+            debug!("User does not have permission or project role");
+            Err(actix_web::HttpResponse::Forbidden()
+                .body(format!(
+                    "Needs permission {:?} or project role {:?}",
+                    &needed_permission.name, needed_role
+                ))
+                .into())
+        }
+    }
+}
+
+/// Role of a user inside a project
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ProjectRole {
+    Member,
+    Admin,
+}
+
+/// Returns the role of a user inside a project. Project owners are always admins.
+/// Returns `None` if the user is no member of the project or the project is deleted.
+pub fn project_role(
+    user_id: i32,
+    project_id: i32,
+    conn: &PgConnection,
+) -> Result<Option<ProjectRole>, diesel::result::Error> {
+    use taskrs_db::schema::{project_members, projects};
+
+    let owner_id = match projects::table
+        .find(project_id)
+        .filter(projects::deleted_at.is_null())
+        .select(projects::owner_id)
+        .first::<i32>(conn)
+        .optional()?
+    {
+        None => return Ok(None),
+        Some(owner_id) => owner_id,
+    };
+
+    if owner_id == user_id {
+        return Ok(Some(ProjectRole::Admin));
+    }
+
+    let is_admin = project_members::table
+        .find((project_id, user_id))
+        .select(project_members::is_admin)
+        .first::<bool>(conn)
+        .optional()?;
+
+    Ok(is_admin.map(|is_admin| {
+        if is_admin {
+            ProjectRole::Admin
+        } else {
+            ProjectRole::Member
+        }
+    }))
 }
 
-/// Update the permission cache for a single user
+/// Update the permission cache for a single user.
+/// Effective permissions are the direct permissions of the user and the permissions of their roles.
 pub fn update_permission_cache_for_user(
     user_id: i32,
     conn: &PgConnection,
 ) -> Result<Vec<String>, diesel::result::Error> {
-    use taskrs_db::schema::{permissions, user_permissions};
+    use taskrs_db::schema::{permissions, role_permissions, user_permissions, user_roles};
 
     debug!("Updating permission cache for user {}", user_id);
     let db_permissions: Vec<String> = permissions::table
-        .inner_join(user_permissions::table)
-        .filter(user_permissions::user_id.eq(user_id))
+        .filter(
+            permissions::id
+                .eq_any(
+                    user_permissions::table
+                        .select(user_permissions::permission_id)
+                        .filter(user_permissions::user_id.eq(user_id)),
+                )
+                .or(permissions::id.eq_any(
+                    role_permissions::table
+                        .inner_join(
+                            user_roles::table.on(user_roles::role_id.eq(role_permissions::role_id)),
+                        )
+                        .select(role_permissions::permission_id)
+                        .filter(user_roles::user_id.eq(user_id)),
+                )),
+        )
         .select(permissions::name)
         .load::<String>(conn)?;
 
@@ -220,3 +372,216 @@ pub fn update_permission_cache_for_user(
 
     Ok(db_permissions)
 }
+
+/// Update the permission cache for all users that have the role
+pub fn update_permission_cache_for_role(
+    role_id: i32,
+    conn: &PgConnection,
+) -> Result<(), diesel::result::Error> {
+    use taskrs_db::schema::user_roles;
+
+    let user_ids: Vec<i32> = user_roles::table
+        .select(user_roles::user_id)
+        .filter(user_roles::role_id.eq(role_id))
+        .load::<i32>(conn)?;
+
+    for user_id in user_ids {
+        update_permission_cache_for_user(user_id, conn)?;
+    }
+
+    Ok(())
+}
+
+/// Checks if the token version of the user is still the current one
+/// Returns InternalServerError on DB error
+pub fn check_token_version(
+    user: &TokenUser,
+    conn: &PgConnection,
+) -> Result<bool, actix_web::Error> {
+    // Check cache
+    if let Some(version) = crate::TOKEN_VERSION_CACHE.read().unwrap().get(&user.id) {
+        return Ok(*version == user.token_version);
+    }
+
+    // Version not found in cache -> Update Cache
+    let version = update_token_version_cache_for_user(user.id, conn).map_err(|err| {
+        error!("{}", err);
+        actix_web::HttpResponse::InternalServerError().finish()
+    })?;
+
+    // Deleted users have no version
+    Ok(version == Some(user.token_version))
+}
+
+/// Update the token version cache for a user
+pub fn update_token_version_cache_for_user(
+    user_id: i32,
+    conn: &PgConnection,
+) -> Result<Option<i32>, diesel::result::Error> {
+    use taskrs_db::schema::users;
+
+    debug!("Updating token version cache for user {}", user_id);
+    let version = users::table
+        .find(user_id)
+        .select(users::token_version)
+        .first::<i32>(conn)
+        .optional()?;
+
+    // Update cache
+    let mut cache = crate::TOKEN_VERSION_CACHE.write().unwrap();
+    match version {
+        Some(version) => cache.insert(user_id, version),
+        None => cache.remove(&user_id),
+    };
+
+    Ok(version)
+}
+
+/// Invalidates all access tokens of the user.
+/// Callers running this in a transaction have to call `invalidate_token_version_cache` after the commit.
+pub fn bump_token_version(user_id: i32, conn: &PgConnection) -> Result<(), diesel::result::Error> {
+    debug!("Bumping token version of user {}", user_id);
+    User::bump_token_version(user_id, conn).optional()?;
+
+    // The transaction may still roll back, so the version is reloaded on the next check
+    invalidate_token_version_cache(user_id);
+
+    Ok(())
+}
+
+/// Removes the token version of a user from the cache.
+/// A check running before a version change is committed caches the old version again,
+/// so this has to be called once more after the commit.
+pub fn invalidate_token_version_cache(user_id: i32) {
+    crate::TOKEN_VERSION_CACHE.write().unwrap().remove(&user_id);
+}
+
+/// Checks if the API token the user authenticated with includes the permission.
+/// Always true for JWT authentication.
+fn api_token_allows(user: &TokenUser, permission: &Permission) -> bool {
+    match &user.api_token_permissions {
+        Some(permissions) => permissions.contains(&permission.name),
+        None => true,
+    }
+}
+
+/// Returns the IP of the client.
+/// `X-Forwarded-For` is only used if the request comes from a trusted proxy,
+/// it is read from the right up to the first address that is not a trusted proxy.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| {
+        CONFIG
+            .server
+            .trusted_proxies
+            .iter()
+            .any(|proxy| proxy.parse::<IpAddr>().ok().as_ref() == Some(ip))
+    };
+
+    let mut client = req.peer_addr()?.ip();
+    if !is_trusted(&client) {
+        return Some(client);
+    }
+
+    let forwarded: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    for addr in forwarded.into_iter().rev() {
+        match addr.trim().parse::<IpAddr>() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+
+        if !is_trusted(&client) {
+            break;
+        }
+    }
+
+    Some(client)
+}
+
+/// Authenticates a bearer token, which is either a JWT access token or a personal API token
+/// Returns None if the token is invalid, expired or revoked
+/// Returns InternalServerError on DB error
+pub fn authenticate(
+    token: &str,
+    conn: &PgConnection,
+) -> Result<Option<TokenUser>, actix_web::Error> {
+    if token.starts_with(API_TOKEN_PREFIX) {
+        return authenticate_api_token(token, conn).map_err(|err| {
+            error!("{}", err);
+            actix_web::HttpResponse::InternalServerError()
+                .finish()
+                .into()
+        });
+    }
+
+    let user = match decode_token(token) {
+        Ok(data) => data.claims.user,
+        Err(err) => {
+            debug!("Error while decoding token: {}", err);
+            return Ok(None);
+        }
+    };
+
+    // Check if token was revoked
+    if !check_token_version(&user, conn)? {
+        debug!("Token version of user {} is outdated", user.id);
+        return Ok(None);
+    }
+
+    Ok(Some(user))
+}
+
+fn authenticate_api_token(
+    token: &str,
+    conn: &PgConnection,
+) -> Result<Option<TokenUser>, diesel::result::Error> {
+    use taskrs_db::schema::{api_token_permissions, api_tokens, permissions, users};
+
+    let api_token = match ApiToken::find_by_hash(&hash_token(token), conn)? {
+        None => {
+            debug!("API token does not exist");
+            return Ok(None);
+        }
+        Some(api_token) => api_token,
+    };
+
+    // Check if token is expired
+    if matches!(api_token.exp, Some(exp) if exp < Utc::now().timestamp()) {
+        debug!("API token {} is expired", api_token.id);
+        return Ok(None);
+    }
+
+    // Check if user is deactivated or deleted
+    let db_user = users::table.find(api_token.user_id).first::<User>(conn)?;
+    if !db_user.can_login() {
+        debug!("User deactivated or deleted");
+        return Ok(None);
+    }
+
+    let token_permissions = permissions::table
+        .inner_join(api_token_permissions::table)
+        .filter(api_token_permissions::api_token_id.eq(api_token.id))
+        .select(permissions::name)
+        .load::<String>(conn)?;
+
+    // Avoid a write on every request
+    let now = Utc::now().naive_utc();
+    if api_token
+        .last_used_at
+        .is_none_or(|last_used_at| (now - last_used_at).num_seconds() > 60)
+    {
+        diesel::update(api_tokens::table.find(api_token.id))
+            .set(api_tokens::last_used_at.eq(now))
+            .execute(conn)?;
+    }
+
+    let mut user: TokenUser = db_user.into();
+    user.api_token_permissions = Some(token_permissions);
+
+    Ok(Some(user))
+}
@@ -12,7 +12,6 @@ export interface User {
 export enum UserColumns {
     Id = 'id',
     Email = 'email',
-    Password = 'password',
     FirstName = 'firstName',
     LastName = 'lastName',
     Activated = 'activated',
@@ -68,18 +68,17 @@ export class AuthService {
         }
     }
 
-    private sendRefreshTokenRequest(refreshToken: string): Observable<string> {
+    private sendRefreshTokenRequest(refreshToken: string): Observable<UserTokens> {
         const headers = new HttpHeaders({
             'Content-Type': 'application/json; charset=utf-8',
         });
 
+        // The refresh token is rotated on every use, so the new one has to be stored as well
         return this.httpClient
-            .post<string>(this.baseUrl + '/token', JSON.stringify(refreshToken), {headers})
+            .post<UserTokens>(this.baseUrl + '/token', JSON.stringify(refreshToken), {headers})
             .pipe(
-                tap(token => {
-                    const expiresAt = decodeJwt(token).exp;
-                    localStorage.setItem('accessToken', token);
-                    localStorage.setItem('accessTokenExp', JSON.stringify(expiresAt));
+                tap(tokens => {
+                    AuthService.setSession(tokens);
                     this.startRefreshTimer();
                 }),
             );